@@ -17,6 +17,43 @@ pub struct Program {
 pub struct Module {
     pub globals: Block,
     pub scenes: Vec<Scene>,
+
+    /// Other modules this one refers to by a shorter name.
+    pub imports: Vec<Import>,
+
+    /// Scenes other modules may use. If absent, they may use all of them.
+    pub exports: Option<Vec<String>>,
+
+    /// Statement templates, usable only within this module.
+    pub macros: Vec<Macro>,
+}
+
+/// Lets `alias:scene` stand for `path:scene`, as in `import std:list as l`.
+/// Without `as`, the alias is the last part of the path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Import {
+    pub path: Modpath,
+    pub alias: String,
+}
+
+/// Statements to paste in wherever `?NAME(args)` is written, as defined by
+/// `given ?NAME(Params)`. See `Program::expand_macros()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macro {
+    pub name: String,
+    pub params: Vec<Option<Ident>>,
+    pub body: Block,
+
+    /// Where the definition starts in the module's source.
+    pub line: u32,
+}
+
+/// A statement about the module as a whole, rather than something to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Declaration {
+    Import(Import),
+    Export(Vec<String>),
+    Macro(Macro),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -26,6 +63,10 @@ pub struct Block(pub Vec<Stmt>);
 pub struct Scene {
     pub name: SceneName,
     pub args: Vec<Option<Ident>>,
+
+    /// What each argument was declared to be, as in `== shop(Stock: list)`.
+    pub arg_types: Vec<Option<Type>>,
+
     pub body: Block,
 }
 
@@ -34,6 +75,20 @@ pub struct WeaveArm {
     pub guard: Cond,
     pub message: Expr,
     pub body: Block,
+
+    /// Whether the arm is withdrawn after it has been chosen once.
+    pub once: bool,
+
+    /// Hints for the host about how to present this choice.
+    pub tags: Vec<Atom>,
+
+    /// If present, the choice is still shown when its guard fails, but it
+    /// can't be picked. Holds the reason to give the player, if any.
+    pub locked: Option<Expr>,
+
+    /// If present, the arm is never offered to the player. Instead, it runs
+    /// when the player hasn't answered after this many seconds.
+    pub timeout: Option<Expr>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -76,12 +131,39 @@ pub enum Stmt {
         value: Expr,
     },
 
+    /// Continues from a section of the same scene.
+    Divert {
+        target: Label,
+    },
+
+    /// Pastes in the body of a macro.
+    Expand {
+        name: String,
+        args: Vec<Expr>,
+    },
+
+    /// What a macro expanded to. Kept together so that errors in the body
+    /// can say which macro it came from.
+    Expanded {
+        name: String,
+        defined_at: u32,
+        body: Block,
+    },
+
     If {
         test: Cond,
         success: Block,
         failure: Block,
     },
 
+    Join {
+        group: Expr,
+    },
+
+    Leave {
+        group: Expr,
+    },
+
     Let {
         value: Expr,
         name: Ident,
@@ -101,18 +183,37 @@ pub enum Stmt {
     Naked {
         message: Str,
         target: Expr,
+        speaker: Option<Atom>,
+        tags: Vec<Atom>,
     },
 
     Recur {
         target: Call,
     },
 
+    Register {
+        name: Expr,
+    },
+
     Return {
         result: bool,
     },
 
     Say {
         message: Expr,
+        speaker: Option<Atom>,
+        tags: Vec<Atom>,
+    },
+
+    /// Changes a variable shared by every actor.
+    SetGlobal {
+        name: String,
+        value: Expr,
+    },
+
+    /// Names a point in the scene which can be diverted to.
+    Section {
+        name: Label,
     },
 
     SendMsg {
@@ -120,15 +221,32 @@ pub enum Stmt {
         target: Expr,
     },
 
+    /// Marks where the following statements appear in the source file.
+    SourceLine {
+        line: u32,
+    },
+
     Trace {
         value: Expr,
     },
 
+    Visit {
+        key: VisitKey,
+    },
+
     Trap {
         name: Label,
         arms: Vec<TrapArm>,
     },
 
+    /// Runs a scene, then comes back here once it unwinds.
+    Tunnel {
+        target: Call,
+    },
+
+    /// Returns to the statement after the most recent tunnel.
+    Unwind,
+
     Wait {
         value: Expr,
     },
@@ -184,27 +302,76 @@ pub enum Expr {
     Arg(u32),
     Atom(Atom),
     Bool(Box<Cond>),
+    Global(String),
+    Group(Box<Expr>),
     Id(Ident),
     Int(i32),
     //Time(u16, TimeUnit),
     Str(Str),
     Splice(Vec<Expr>),
+
+    /// Fills each `{N}` in a string with the Nth of the other values.
+    Format(Box<Expr>, Vec<Expr>),
+
     Op(Op, Vec<Expr>),
     List(Vec<Expr>),
     MenuChoice(Vec<Expr>),
     Nth(Box<Expr>, u32),
+
+    /// An item of a list, counting from zero, as in `Party[I]`.
+    Index(Box<Expr>, Box<Expr>),
+
+    /// Everything in a list after the first N items, or an empty list if
+    /// there aren't enough.
+    Drop(Box<Expr>, u32),
+
+    /// A call to a function provided by the runtime, as in `length(Party)`.
+    Builtin(Builtin, Vec<Expr>),
+
+    /// A map from atoms to values, as in `{name: #bob, hp: 10}`.
+    Map(Vec<(Atom, Expr)>),
+
+    /// A copy of a map with some entries replaced, as in `{Bob | hp: 9}`.
+    Update(Box<Expr>, Vec<(Atom, Expr)>),
+
+    /// The value of one map entry, as in `Bob.hp`.
+    Field(Box<Expr>, Atom),
+
+    /// Like `Field`, but leaves the result undefined instead of failing if
+    /// the entry is missing. Patterns use it to look inside map entries.
+    /// Their tests run in order and stop at the first one that fails, so the
+    /// entry has always been checked for by then; this only keeps a mistake
+    /// in that ordering from stopping the actor.
+    TryField(Box<Expr>, Atom),
+
     Spawn(Call),
+    Vary(VarySite),
+    Visits(VisitKey),
     PidOfSelf,
     PidZero,
     Infinity,
 }
 
+/// Something whose visits are counted by the runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VisitKey {
+    Scene(SceneName),
+    Choice(Label, u32),
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Cond {
     True,
     False,
     LastResort,
     HasLength(Expr, u32),
+    MinLength(Expr, u32),
+    HasKey(Expr, Atom),
+    HasType(Expr, Type),
+
+    /// Holds if the value is `true`, and fails if it isn't a boolean.
+    IsTrue(Expr),
+
     Compare(BoolOp, Expr, Expr),
     And(Vec<Cond>),
     Or(Vec<Cond>),
@@ -217,6 +384,14 @@ pub enum Pat {
     Assign(Ident),
     Match(Expr),
     List(Vec<Pat>),
+
+    /// Matches the first items of a list, then the rest, as in `[H | T]`.
+    Cons(Vec<Pat>, Box<Pat>),
+
+    Map(Vec<(Atom, Pat)>),
+
+    /// Matches only values of one type, as in `Count: int`.
+    Typed(Box<Pat>, Type),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -227,6 +402,38 @@ pub enum Atom {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Str {
     Plain(String),
+    Varied(Vec<Fragment>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fragment {
+    Text(String),
+    Vary(Variation),
+}
+
+/// Inline alternatives, one of which is chosen each time the text is said.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variation {
+    pub kind: VariationKind,
+    pub per_actor: bool,
+    pub items: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VariationKind {
+    Sequence,
+    Cycle,
+    Once,
+    Shuffle,
+}
+
+/// A variation which has been named after the place it was written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarySite {
+    pub name: String,
+    pub kind: VariationKind,
+    pub per_actor: bool,
+    pub items: Vec<String>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -238,6 +445,28 @@ pub enum Op {
     Roll,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Builtin {
+    Length,
+    Append,
+    Concat,
+    ToString,
+    Roll,
+    Member,
+}
+
+/// The kinds of value a script can declare, as in `Stock: list`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    Atom,
+    Str,
+    List,
+    Actor,
+    Map,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum BoolOp {
     Eql,
@@ -245,6 +474,11 @@ pub enum BoolOp {
     Lt,
     Gte,
     Lte,
+
+    /// Like `Eql`, but bools and ints are unequal rather than an error.
+    /// Literals in patterns and trap guards use this, since they can meet
+    /// anything.
+    Same,
 }
 
 use lalrpop_util::ParseError;
@@ -261,6 +495,179 @@ impl Module {
     }
 }
 
+impl Builtin {
+    pub fn named(name: &str) -> Option<Self> {
+        Some(match name {
+            "length" => Builtin::Length,
+            "append" => Builtin::Append,
+            "concat" => Builtin::Concat,
+            "to_string" => Builtin::ToString,
+            "roll" => Builtin::Roll,
+            "member" => Builtin::Member,
+            _ => return None,
+        })
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            &Builtin::Length => 1,
+            &Builtin::Append => 2,
+            &Builtin::Concat => 2,
+            &Builtin::ToString => 1,
+            &Builtin::Roll => 2,
+            &Builtin::Member => 2,
+        }
+    }
+
+    /// Whether calling the built-in leaves everything as it was, so it can
+    /// be used in a guard.
+    pub fn is_pure(&self) -> bool {
+        match self {
+            &Builtin::Roll => false,
+            _ => true,
+        }
+    }
+}
+
+impl Type {
+    pub fn named(name: &str) -> Option<Self> {
+        Some(match name {
+            "int" => Type::Int,
+            "bool" => Type::Bool,
+            "atom" => Type::Atom,
+            "string" => Type::Str,
+            "list" => Type::List,
+            "actor" => Type::Actor,
+            "map" => Type::Map,
+            _ => return None,
+        })
+    }
+}
+
+impl Str {
+    /// Splits the contents of a string literal into plain text and
+    /// variations, which are written as `{a|b|c}`. A sigil at the start
+    /// picks how the alternatives are used: `&` cycles, `!` runs out, `~`
+    /// shuffles, and no sigil stops at the last one. Adding `@` before the
+    /// sigil keeps a separate count for each actor.
+    pub fn parse(text: &str) -> Self {
+        let mut fragments = vec![];
+        let mut plain = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find(|c: char| c == '{' || c == '\\') {
+            plain.push_str(&rest[.. start]);
+            rest = &rest[start ..];
+
+            if rest.starts_with('\\') {
+                // Markup tags keep their braces
+                let tag_end = rest[1 ..].find(|c: char| !c.is_alphanumeric())
+                    .map(|i| i + 1)
+                    .unwrap_or(rest.len());
+
+                let end = if rest[tag_end ..].starts_with('{') {
+                    rest.find('}').map(|i| i + 1).unwrap_or(rest.len())
+                } else {
+                    tag_end
+                };
+
+                plain.push_str(&rest[.. end]);
+                rest = &rest[end ..];
+                continue;
+            }
+
+            let end = match rest.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+
+            match Variation::parse(&rest[1 .. end]) {
+                Some(variation) => {
+                    if !plain.is_empty() {
+                        fragments.push(Fragment::Text(plain.clone()));
+                        plain.clear();
+                    }
+
+                    fragments.push(Fragment::Vary(variation));
+                },
+
+                None => plain.push_str(&rest[.. end + 1]),
+            }
+
+            rest = &rest[end + 1 ..];
+        }
+
+        plain.push_str(rest);
+
+        if fragments.is_empty() {
+            return Str::Plain(plain);
+        }
+
+        if !plain.is_empty() {
+            fragments.push(Fragment::Text(plain));
+        }
+
+        Str::Varied(fragments)
+    }
+
+    pub fn into_fragments(self) -> Vec<Fragment> {
+        match self {
+            Str::Plain(text) => vec![Fragment::Text(text)],
+            Str::Varied(fragments) => fragments,
+        }
+    }
+}
+
+/// Splits `#tag` annotations off the end of a line of text.
+pub fn split_tags(text: &str) -> (&str, Vec<Atom>) {
+    let mut rest = text.trim_end();
+    let mut tags = vec![];
+
+    while let Some(start) = rest.rfind(char::is_whitespace) {
+        let word = &rest[start + 1 ..];
+
+        let is_tag = word.starts_with('#') && word.len() > 1 && {
+            word[1 ..].chars().all(|c| {
+                c == '_' || c.is_lowercase() || c.is_digit(10)
+            })
+        };
+
+        if !is_tag { break; }
+
+        tags.push(Atom::User(word[1 ..].to_owned()));
+        rest = rest[.. start].trim_end();
+    }
+
+    tags.reverse();
+    (rest, tags)
+}
+
+impl Variation {
+    fn parse(body: &str) -> Option<Self> {
+        let per_actor = body.starts_with('@');
+        let body = if per_actor { &body[1 ..] } else { body };
+
+        let (kind, body) = match body.chars().next() {
+            Some('&') => (VariationKind::Cycle, &body[1 ..]),
+            Some('!') => (VariationKind::Once, &body[1 ..]),
+            Some('~') => (VariationKind::Shuffle, &body[1 ..]),
+            _ => (VariationKind::Sequence, body),
+        };
+
+        let explicit = per_actor || kind != VariationKind::Sequence;
+
+        if !explicit && !body.contains('|') {
+            return None;
+        }
+
+        Some(Variation {
+            kind: kind,
+            per_actor: per_actor,
+            items: body.split('|').map(|item| item.to_owned()).collect(),
+        })
+    }
+}
+
 impl Default for Pat {
     fn default() -> Self {
         Pat::Hole
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use ast::*;
 use ast::visit::*;
@@ -9,6 +9,7 @@ impl Program {
     pub fn check_names(&self) -> Try<()> {
         let mut pass = Pass {
             defs: HashMap::new(),
+            modules: HashSet::new(),
             context: ErrCtx::NoContext,
             errors: Vec::new(),
         };
@@ -26,10 +27,14 @@ impl Program {
 struct SceneDef {
     args_wanted: usize,
     times_called: usize,
+
+    /// Whether scenes in other modules may refer to this one.
+    public: bool,
 }
 
 struct Pass {
     defs: HashMap<QfdSceneName, SceneDef>,
+    modules: HashSet<Modpath>,
     context: ErrCtx,
     errors: Vec<BuildErrWithCtx>,
 }
@@ -49,7 +54,7 @@ impl Pass {
         self.errors.push(BuildErrWithCtx(err, self.context.clone()));
     }
 
-    fn def_scene(&mut self, t: &Scene, modpath: &Modpath) -> Try<()> {
+    fn def_scene(&mut self, t: &Scene, modpath: &Modpath, public: bool) -> Try<()> {
         let &SceneName { ref name, ref in_module } = &t.name;
 
         let qualified = QfdSceneName {
@@ -69,11 +74,46 @@ impl Pass {
             self.defs.insert(qualified, SceneDef {
                 args_wanted: t.args.len(),
                 times_called: 0,
+                public: public,
             });
         }
 
         Ok(())
     }
+
+    fn check_exports(&mut self, module: &Module, modpath: &Modpath) {
+        self.context.begin_module(modpath);
+
+        for name in module.exports.iter().flat_map(|names| names.iter()) {
+            let qualified = QfdSceneName {
+                name: name.clone(),
+                in_module: modpath.clone(),
+            };
+
+            if !self.defs.contains_key(&qualified) {
+                self.push_err(BuildErr::NoSuchScene(qualified));
+            }
+        }
+    }
+
+    /// Finds the definition of a scene, or says why it can't be used here.
+    fn lookup(&mut self, name: &SceneName) -> Try<Result<&mut SceneDef, BuildErr>> {
+        let qualified = self.qualify(name)?;
+        let here = self.context.modpath()?;
+
+        if !self.modules.contains(&qualified.in_module) {
+            return Ok(Err(BuildErr::NoSuchModule(qualified.in_module)));
+        }
+
+        Ok(match self.defs.get_mut(&qualified) {
+            Some(ref def) if !def.public && qualified.in_module != here => {
+                Err(BuildErr::SceneIsPrivate(qualified))
+            },
+
+            Some(def) => Ok(def),
+            None => Err(BuildErr::NoSuchScene(qualified)),
+        })
+    }
 }
 
 impl Visitor for Pass {
@@ -84,11 +124,22 @@ impl Visitor for Pass {
     fn visit_program(&mut self, t: &Program) -> Try<()> {
         // Stage 1: Collect scene names
         for &(ref modpath, ref module) in t.modules.iter() {
+            self.modules.insert(modpath.clone());
+
             for scene in module.scenes.iter() {
-                self.def_scene(scene, modpath)?;
+                let public = match module.exports.as_ref() {
+                    Some(names) => names.contains(&scene.name.name),
+                    None => true,
+                };
+
+                self.def_scene(scene, modpath, public)?;
             }
         }
 
+        for &(ref modpath, ref module) in t.modules.iter() {
+            self.check_exports(module, modpath);
+        }
+
         // Stage 2: Check argument counts
         for &(ref modpath, ref module) in t.modules.iter() {
             self.visit_module(module, modpath)?;
@@ -99,10 +150,9 @@ impl Visitor for Pass {
 
     fn visit_call(&mut self, t: &Call) -> Try<()> {
         let &Call(ref name, ref args) = t;
-        let qualified = self.qualify(name)?;
 
-        let err = match self.defs.get_mut(&qualified) {
-            Some(def) => {
+        let err = match self.lookup(name)? {
+            Ok(def) => {
                 def.times_called += 1;
                 if args.len() != def.args_wanted {
                     Some(BuildErr::WrongNumberOfArgs {
@@ -114,7 +164,7 @@ impl Visitor for Pass {
                     None
                 }
             },
-            None => { Some(BuildErr::NoSuchScene(qualified.clone())) },
+            Err(err) => Some(err),
         };
 
         if let Some(err) = err {
@@ -123,4 +173,26 @@ impl Visitor for Pass {
 
         Ok(())
     }
+
+    fn visit_builtin(&mut self, t: &Builtin, args: &[Expr]) -> Try<()> {
+        if args.len() != t.arity() {
+            self.push_err(BuildErr::WrongNumberOfBuiltinArgs {
+                builtin: *t,
+                wanted: t.arity(),
+                got: args.len(),
+            });
+        }
+
+        each(args, |t| self.visit_expr(t))
+    }
+
+    fn visit_visit_key(&mut self, t: &VisitKey) -> Try<()> {
+        if let &VisitKey::Scene(ref name) = t {
+            if let Err(err) = self.lookup(name)? {
+                self.push_err(err);
+            }
+        }
+
+        Ok(())
+    }
 }
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use ast::*;
 use ast::pass::*;
 use ast::rewrite::*;
@@ -37,19 +35,31 @@ impl Pass {
         };
 
         for arm in t.into_iter().rev() {
-            let rewriter = RwPat {
-                bindings: HashMap::new(),
+            let mut rewriter = RwPat {
+                bindings: vec![],
                 tests: vec![],
                 path: vec![],
                 root: v.clone(),
             };
 
-            let mut tests = rewriter.pat_to_cond(arm.pattern)?;
-            tests.push(arm.guard);
+            rewriter.walk_pat(arm.pattern)?;
+            let guard = rewriter.rw_cond(arm.guard)?;
+
+            let mut tests = rewriter.tests;
+            tests.push(guard);
+
+            let mut body = rewriter.bindings.into_iter().map(|(name, value)| {
+                Stmt::Let {
+                    name: name,
+                    value: value,
+                }
+            }).collect::<Vec<_>>();
+
+            body.extend(self.rw_block(arm.body)?.0);
 
             tail = Stmt::If {
                 test: Cond::And(tests),
-                success: self.rw_block(arm.body)?,
+                success: Block(body),
                 failure: Block(vec![tail]),
             };
         }
@@ -87,16 +97,24 @@ impl Rewriter for Pass {
 }
 
 struct RwPat {
-    bindings: HashMap<Ident, Expr>,
+    bindings: Vec<(Ident, Expr)>,
     tests: Vec<Cond>,
-    path: Vec<usize>,
+    path: Vec<Step>,
     root: Expr,
 }
 
+/// How to reach part of the value being matched.
+enum Step {
+    Nth(u32),
+    Drop(u32),
+    Key(Atom),
+}
+
 impl RwPat {
-    fn pat_to_cond(mut self, t: Pat) -> Try<Vec<Cond>> {
-        self.walk_pat(t)?;
-        Ok(self.tests)
+    fn lookup(&self, id: &Ident) -> Option<&Expr> {
+        self.bindings.iter()
+            .find(|&&(ref name, _)| name == id)
+            .map(|&(_, ref expr)| expr)
     }
 
     fn walk_pat(&mut self, t: Pat) -> Try<()> {
@@ -104,15 +122,18 @@ impl RwPat {
             Pat::Hole => (),
 
             Pat::Assign(id) => {
-                if self.bindings.contains_key(&id) {
+                if self.lookup(&id).is_some() {
                     ice!("Shadowed assignment to {} in pattern", &id);
                 }
+
+                let path = self.path_expr();
+                self.bindings.push((id, path));
             },
 
             Pat::Match(expr) => {
                 let expr = self.rw_expr(expr)?;
                 let path = self.path_expr();
-                self.tests.push(Cond::Compare(BoolOp::Eql, expr, path));
+                self.tests.push(Cond::Compare(BoolOp::Same, expr, path));
             },
 
             Pat::List(patterns) => {
@@ -120,7 +141,40 @@ impl RwPat {
                 self.tests.push(Cond::HasLength(path, patterns.len() as u32));
 
                 for (i, pattern) in patterns.into_iter().enumerate() {
-                    self.path.push(i);
+                    self.path.push(Step::Nth(i as u32));
+                    self.walk_pat(pattern)?;
+                    self.path.pop();
+                }
+            },
+
+            Pat::Cons(patterns, rest) => {
+                let path = self.path_expr();
+                let len = patterns.len() as u32;
+                self.tests.push(Cond::MinLength(path, len));
+
+                for (i, pattern) in patterns.into_iter().enumerate() {
+                    self.path.push(Step::Nth(i as u32));
+                    self.walk_pat(pattern)?;
+                    self.path.pop();
+                }
+
+                self.path.push(Step::Drop(len));
+                self.walk_pat(*rest)?;
+                self.path.pop();
+            },
+
+            Pat::Typed(pattern, ty) => {
+                let path = self.path_expr();
+                self.tests.push(Cond::HasType(path, ty));
+                self.walk_pat(*pattern)?;
+            },
+
+            Pat::Map(entries) => {
+                for (key, pattern) in entries.into_iter() {
+                    let path = self.path_expr();
+                    self.tests.push(Cond::HasKey(path, key.clone()));
+
+                    self.path.push(Step::Key(key));
                     self.walk_pat(pattern)?;
                     self.path.pop();
                 }
@@ -130,8 +184,14 @@ impl RwPat {
 
     fn path_expr(&self) -> Expr {
         let mut root = self.root.clone();
-        for &i in self.path.iter() {
-            root = Expr::Nth(Box::new(root), i as u32);
+        for step in self.path.iter() {
+            root = match step {
+                &Step::Nth(i) => Expr::Nth(Box::new(root), i),
+                &Step::Drop(n) => Expr::Drop(Box::new(root), n),
+                &Step::Key(ref key) => {
+                    Expr::TryField(Box::new(root), key.clone())
+                },
+            };
         }
         root
     }
@@ -139,7 +199,7 @@ impl RwPat {
 
 impl Rewriter for RwPat {
     fn rw_id_eval(&mut self, t: Ident) -> Try<Expr> {
-        if let Some(expr) = self.bindings.get(&t).cloned() {
+        if let Some(expr) = self.lookup(&t).cloned() {
             Ok(expr)
         } else {
             Ok(Expr::Id(t))
@@ -6,13 +6,78 @@ use driver::Try;
 
 impl DesugaredProgram {
     pub fn desugar_naked(self) -> Try<Self> {
-        Pass.rw_desugared(self)
+        let mut pass = Pass {
+            prefix: String::new(),
+            sites: 0,
+        };
+
+        pass.rw_desugared(self)
     }
 }
 
-struct Pass;
+struct Pass {
+    /// Name of the scene or module whose variation sites are being named.
+    prefix: String,
+    sites: u32,
+}
+
+impl Pass {
+    fn enter(&mut self, prefix: String) {
+        self.prefix = prefix;
+        self.sites = 0;
+    }
+
+    /// Turns each variation into a `{N}` placeholder, so the text can be
+    /// translated as a whole, and gives every variation a name so the
+    /// runtime can tell how often it has been used.
+    fn vary(&mut self, fragments: Vec<Fragment>) -> Expr {
+        let mut template = String::new();
+        let mut args: Vec<Expr> = vec![];
+
+        for fragment in fragments {
+            match fragment {
+                Fragment::Text(text) => template.push_str(&text),
+
+                Fragment::Vary(variation) => {
+                    template.push_str(&format!("{{{}}}", args.len()));
+
+                    args.push(Expr::Vary(VarySite {
+                        name: format!("{}~{}", self.prefix, self.sites),
+                        kind: variation.kind,
+                        per_actor: variation.per_actor,
+                        items: variation.items,
+                    }));
+
+                    self.sites += 1;
+                },
+            }
+        }
+
+        let template = Expr::Str(Str::Plain(template));
+
+        if args.is_empty() {
+            template
+        } else {
+            Expr::Format(Box::new(template), args)
+        }
+    }
+}
 
 impl Rewriter for Pass {
+    fn rw_desugared(&mut self, t: DesugaredProgram) -> Try<DesugaredProgram> {
+        Ok(DesugaredProgram {
+            preludes: each(t.preludes, |(modpath, t)| {
+                self.enter(format!("{}", modpath));
+                Ok((modpath, self.rw_block(t)?))
+            })?,
+            scenes: each(t.scenes, |t| {
+                self.enter(format!("{}", t.name.qualified()?));
+                self.rw_scene(t)
+            })?,
+            lambdas: each(t.lambdas, |t| self.rw_lambda(t))?,
+        })
+    }
+
     fn rw_block(&mut self, t: Block) -> Try<Block> {
         let Block(mut stack) = t;
         let mut output = Vec::with_capacity(stack.len());
@@ -21,19 +86,34 @@ impl Rewriter for Pass {
 
         while let Some(stmt) = stack.pop() {
             match stmt {
-                Stmt::Naked { target, message } => {
-                    let mut text = match message {
-                        Str::Plain(text) => text,
-                    };
+                Stmt::Naked { target, message, speaker, mut tags } => {
+                    let mut fragments = message.into_fragments();
 
+                    // A line with its own speaker starts a new paragraph
                     while let Some(stmt) = stack.pop() {
                         match stmt {
                             Stmt::Naked {
                                 target: Expr::PidZero,
-                                message: Str::Plain(next_line),
+                                message: next_line,
+                                speaker: None,
+                                tags: more_tags,
                             } => {
-                                text.push(' ');
-                                text.push_str(&next_line);
+                                fragments.push(Fragment::Text(" ".to_owned()));
+                                fragments.extend(next_line.into_fragments());
+                                tags.extend(more_tags);
+                            },
+
+                            Stmt::SourceLine { line } => match stack.last() {
+                                Some(&Stmt::Naked {
+                                    target: Expr::PidZero,
+                                    speaker: None,
+                                    ..
+                                }) => continue,
+
+                                _ => {
+                                    stack.push(Stmt::SourceLine { line: line });
+                                    break;
+                                },
                             },
 
                             other => {
@@ -49,7 +129,9 @@ impl Rewriter for Pass {
                     };
 
                     output.push(Stmt::Say {
-                        message: Expr::Str(Str::Plain(text)),
+                        message: self.vary(fragments),
+                        speaker: speaker,
+                        tags: tags,
                     });
                 },
 
@@ -59,6 +141,11 @@ impl Rewriter for Pass {
 
         Ok(Block(output))
     }
+
+    fn rw_str(&mut self, t: Str) -> Try<Expr> {
+        // Choice text and strings kept in variables vary just like lines
+        Ok(self.vary(t.into_fragments()))
+    }
 }
 
 #[test]
@@ -30,8 +30,8 @@ struct Pass {
 }
 
 impl Pass {
-    fn rw_trap(&mut self, l: Label, a: Vec<TrapArm>) -> Try<Stmt> {
-        let body = Block(vec!{
+    fn rw_trap(&mut self, l: Label, a: Vec<TrapArm>, at: Option<u32>) -> Try<Stmt> {
+        let mut body = Block(vec!{
             Stmt::Match {
                 value: Expr::List(vec!{
                     Expr::Arg(0),
@@ -44,7 +44,7 @@ impl Pass {
                         arm.origin,
                     }),
 
-                    guard: arm.guard,
+                    guard: lenient(arm.guard),
                     body: arm.body,
                 }).collect(),
 
@@ -52,6 +52,10 @@ impl Pass {
             },
         });
 
+        if let Some(line) = at {
+            body.0.insert(0, Stmt::SourceLine { line: line });
+        }
+
         let mut lambda = TrapLambda {
             label: l.clone(),
             captures: vec![],
@@ -70,15 +74,37 @@ impl Pass {
     }
 }
 
+/// Lets `==` in a trap's guard meet a value of any type. A trap can be sent
+/// anything, so a message of the wrong type should skip the arm rather than
+/// stop the actor.
+fn lenient(t: Cond) -> Cond {
+    match t {
+        Cond::Compare(BoolOp::Eql, lhs, rhs) => {
+            Cond::Compare(BoolOp::Same, lhs, rhs)
+        },
+
+        Cond::And(conds) => Cond::And(conds.into_iter().map(lenient).collect()),
+        Cond::Or(conds) => Cond::Or(conds.into_iter().map(lenient).collect()),
+        Cond::Not(cond) => Cond::Not(Box::new(lenient(*cond))),
+        other => other,
+    }
+}
+
 impl Rewriter for Pass {
     fn rw_block(&mut self, t: Block) -> Try<Block> {
         let Block(input) = t;
         let mut output = Vec::with_capacity(input.len());
+        let mut at = None;
 
         for stmt in input {
             match stmt {
                 Stmt::Trap { name, arms } => {
-                    output.push(self.rw_trap(name, arms)?);
+                    output.push(self.rw_trap(name, arms, at)?);
+                },
+
+                Stmt::SourceLine { line } => {
+                    at = Some(line);
+                    output.push(Stmt::SourceLine { line: line });
                 },
 
                 other => output.push(self.rw_stmt(other)?),
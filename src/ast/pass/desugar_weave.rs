@@ -20,29 +20,67 @@ impl Pass {
         let mut or_else = Block(vec![]);
 
         for (i, arm) in a.into_iter().enumerate() {
-            choices.push(match arm.guard {
+            let key = VisitKey::Choice(l.clone(), i as u32);
+
+            // The runtime tells a timer apart from a choice by its length
+            if let Some(seconds) = arm.timeout {
+                choices.push(Expr::List(vec![Expr::Int(i as i32), seconds]));
+
+                arms.push(MatchArm {
+                    pattern: Pat::Match(Expr::Int(i as i32)),
+                    guard: Cond::True,
+                    body: self.rw_block(arm.body)?,
+                });
+
+                continue;
+            }
+
+            let unused = Cond::Compare(BoolOp::Eql, Expr::Visits(key.clone()), {
+                Expr::Int(0)
+            });
+
+            let (guard, visible) = match arm.guard {
                 Cond::LastResort => {
                     or_else = arm.body;
                     continue;
                 },
 
-                other => Expr::List(vec![
-                    Expr::Bool(Box::new(other)),
-                    Expr::Int(i as i32),
-                    arm.message,
-                ]),
-            });
+                // A locked choice stays on the menu until it's used up
+                other if arm.locked.is_some() => {
+                    (other, if arm.once { unused } else { Cond::True })
+                },
+
+                other if arm.once => (Cond::And(vec![other, unused]), {
+                    Cond::True
+                }),
+
+                other => (other, Cond::True),
+            };
+
+            let mut item = vec![
+                Expr::Bool(Box::new(guard)),
+                Expr::Int(i as i32),
+                arm.message,
+                Expr::List(arm.tags.into_iter().map(Expr::Atom).collect()),
+            ];
+
+            if let Some(reason) = arm.locked {
+                item.push(Expr::Bool(Box::new(visible)));
+                item.push(reason);
+            }
+
+            choices.push(Expr::List(item));
+
+            let Block(mut body) = self.rw_block(arm.body)?;
+            body.insert(0, Stmt::Visit { key: key });
 
             arms.push(MatchArm {
                 pattern: Pat::Match(Expr::Int(i as i32)),
                 guard: Cond::True,
-                body: self.rw_block(arm.body)?,
+                body: Block(body),
             });
         }
 
-        // FIXME: Do we still want weaves to have labels at all?
-        let _ = l;
-
         Ok(Stmt::Match {
             value: Expr::MenuChoice(choices),
             arms: arms,
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, HashSet};
+
+use ast::*;
+use ast::rewrite::*;
+use ast::visit::Visitor;
+
+use driver::{Try, BuildErr, BuildErrWithCtx, ErrCtx};
+
+impl Program {
+    /// Replaces each use of a macro with a copy of its body. Variables which
+    /// the body assigns to are renamed in each copy, so they can't clash with
+    /// the ones around the use site.
+    pub fn expand_macros(self) -> Try<Self> {
+        let mut errors = vec![];
+        let mut modules = Vec::with_capacity(self.modules.len());
+
+        for (modpath, module) in self.modules.into_iter() {
+            let mut pass = Pass {
+                macros: HashMap::new(),
+                context: ErrCtx::NoContext,
+                errors: vec![],
+                expanding: vec![],
+                line: 0,
+                gensyms: 0,
+                assigned: HashSet::new(),
+                hidden: HashMap::new(),
+            };
+
+            pass.context.begin_module(&modpath);
+
+            for def in module.macros.iter() {
+                if pass.macros.contains_key(&def.name) {
+                    let err = BuildErr::MacroRedefined(def.name.clone());
+                    pass.errors.push(err.with_ctx(&pass.context));
+                } else {
+                    pass.macros.insert(def.name.clone(), def.clone());
+                }
+            }
+
+            let globals = pass.rw_block(module.globals)?;
+
+            let scenes = each(module.scenes, |t| {
+                pass.context.begin_scene(&t.name.name)?;
+                pass.assigned.clear();
+                pass.hidden.clear();
+                pass.rw_scene(t)
+            })?;
+
+            errors.extend(pass.errors);
+
+            modules.push((modpath, Module {
+                globals: globals,
+                scenes: scenes,
+                imports: module.imports,
+                exports: module.exports,
+                macros: module.macros,
+            }));
+        }
+
+        if errors.len() > 0 {
+            return Err(errors.into());
+        }
+
+        Ok(Program {
+            modules: modules,
+        })
+    }
+}
+
+struct Pass {
+    macros: HashMap<String, Macro>,
+    context: ErrCtx,
+    errors: Vec<BuildErrWithCtx>,
+
+    /// Macros whose bodies are being expanded, innermost last.
+    expanding: Vec<String>,
+
+    /// Source line of the statement being rewritten.
+    line: u32,
+
+    gensyms: u32,
+
+    /// Variables bound in the scene being rewritten.
+    assigned: HashSet<Ident>,
+
+    /// Variables which only the bodies of macros used in this scene bind,
+    /// with the macro's name, where it was defined and where it was used.
+    hidden: HashMap<Ident, (String, u32, u32)>,
+}
+
+impl Pass {
+    fn push_err(&mut self, err: BuildErr, site: &Stmt) -> Try<()> {
+        let mut context = self.context.clone();
+        context.push_stmt(site)?;
+        self.errors.push(err.with_ctx(&context));
+        Ok(())
+    }
+
+    fn expand(&mut self, name: String, args: Vec<Expr>) -> Try<Stmt> {
+        let site = Stmt::Expand {
+            name: name.clone(),
+            args: args.clone(),
+        };
+
+        let def = match self.macros.get(&name) {
+            Some(def) => def.clone(),
+
+            None => {
+                self.push_err(BuildErr::NoSuchMacro(name), &site)?;
+                return Ok(Stmt::Empty);
+            },
+        };
+
+        if args.len() != def.params.len() {
+            self.push_err(BuildErr::WrongNumberOfMacroArgs {
+                name: name,
+                defined_at: def.line,
+                used_at: self.line,
+                wanted: def.params.len(),
+                got: args.len(),
+            }, &site)?;
+
+            return Ok(Stmt::Empty);
+        }
+
+        if self.expanding.contains(&name) {
+            self.push_err(BuildErr::RecursiveMacro {
+                name: name,
+                defined_at: def.line,
+            }, &site)?;
+
+            return Ok(Stmt::Empty);
+        }
+
+        self.gensyms += 1;
+
+        let mut hygiene = Hygiene {
+            context: self.context.clone(),
+            suffix: format!("%{:04X}%Macro", self.gensyms),
+            bound: HashSet::new(),
+        };
+
+        for param in def.params.iter() {
+            if let &Some(ref id) = param {
+                hygiene.bound.insert(id.clone());
+            }
+        }
+
+        hygiene.visit_block(&def.body)?;
+
+        for id in hygiene.bound.iter() {
+            self.hidden.insert(id.clone(), (name.clone(), def.line, self.line));
+        }
+
+        let mut body = Vec::with_capacity(args.len() + def.body.0.len());
+
+        for (param, arg) in def.params.into_iter().zip(args.into_iter()) {
+            body.push(match param {
+                Some(id) => Stmt::Let {
+                    name: hygiene.rename(id),
+                    value: arg,
+                },
+
+                None => Stmt::Discard {
+                    value: arg,
+                },
+            });
+        }
+
+        body.extend(hygiene.rw_block(def.body)?.0);
+
+        // Errors in nested macros should point back to this use, too
+        let line = self.line;
+        self.context.push_stmt(&site)?;
+        self.expanding.push(name.clone());
+        let body = self.rw_block(Block(body))?;
+        self.expanding.pop();
+        self.context.pop()?;
+        self.line = line;
+
+        Ok(Stmt::Expanded {
+            name: name,
+            defined_at: def.line,
+            body: body,
+        })
+    }
+}
+
+impl Rewriter for Pass {
+    fn rw_block(&mut self, t: Block) -> Try<Block> {
+        let Block(input) = t;
+        let mut output = Vec::with_capacity(input.len());
+
+        for stmt in input {
+            output.push(match stmt {
+                Stmt::Expand { name, args } => self.expand(name, args)?,
+
+                Stmt::SourceLine { line } => {
+                    self.line = line;
+                    Stmt::SourceLine { line: line }
+                },
+
+                other => self.rw_stmt(other)?,
+            });
+        }
+
+        Ok(Block(output))
+    }
+
+    fn rw_id_eval(&mut self, t: Ident) -> Try<Expr> {
+        if !self.assigned.contains(&t) {
+            if let Some(&(ref name, defined_at, expanded_at)) = self.hidden.get(&t) {
+                let err = BuildErr::VarOnlyInMacro {
+                    var: t.name.clone(),
+                    name: name.clone(),
+                    defined_at: defined_at,
+                    expanded_at: expanded_at,
+                    used_at: self.line,
+                };
+
+                self.errors.push(err.with_ctx(&self.context));
+            }
+        }
+
+        Ok(Expr::Id(t))
+    }
+
+    fn rw_id_assign(&mut self, t: Ident) -> Try<Ident> {
+        self.assigned.insert(t.clone());
+        Ok(t)
+    }
+}
+
+/// Renames the variables a macro body binds.
+struct Hygiene {
+    context: ErrCtx,
+    suffix: String,
+    bound: HashSet<Ident>,
+}
+
+impl Hygiene {
+    fn rename(&self, t: Ident) -> Ident {
+        if self.bound.contains(&t) {
+            Ident {
+                name: format!("{}{}", t.name, self.suffix),
+            }
+        } else {
+            t
+        }
+    }
+}
+
+impl Visitor for Hygiene {
+    fn error_context(&mut self) -> &mut ErrCtx {
+        &mut self.context
+    }
+
+    fn visit_id_assign(&mut self, t: &Ident) -> Try<()> {
+        self.bound.insert(t.clone());
+        Ok(())
+    }
+}
+
+impl Rewriter for Hygiene {
+    fn rw_id_eval(&mut self, t: Ident) -> Try<Expr> {
+        Ok(Expr::Id(self.rename(t)))
+    }
+
+    fn rw_id_assign(&mut self, t: Ident) -> Try<Ident> {
+        Ok(self.rename(t))
+    }
+}
+
+#[test]
+fn reject_vars_bound_only_in_macros() {
+    let source = include_str!("../../../tests/invalid/macro_hygiene.svr");
+    let module = Module::parse(source).unwrap();
+
+    let program = Program {
+        modules: vec![
+            (Modpath(vec!["test".to_owned()]), module),
+        ],
+    };
+
+    let errs = match program.expand_macros() {
+        Err(::driver::CompileErr::BuildErrs(errs)) => errs,
+        Err(other) => panic!("Unexpected error {:?}", other),
+        Ok(_) => panic!("Price should not be visible outside ?SET_PRICE"),
+    };
+
+    let vars = errs.iter().map(|err| match err.0 {
+        BuildErr::VarOnlyInMacro { ref var, ref name, .. } => {
+            (var.clone(), name.clone())
+        },
+
+        ref other => panic!("Unexpected error {:?}", other),
+    }).collect::<Vec<_>>();
+
+    assert_eq!(vars, vec![("Price".to_owned(), "SET_PRICE".to_owned())]);
+}
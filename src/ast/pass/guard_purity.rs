@@ -0,0 +1,99 @@
+use ast::*;
+use ast::visit::*;
+
+use driver::{Try, ErrCtx, BuildErr, BuildErrWithCtx};
+
+impl Program {
+    /// Checks that trap and weave guards only look at things. Guards are
+    /// tested whenever a message arrives or a menu is shown, in no promised
+    /// order, so one which changed anything would make the story depend on
+    /// when that happened.
+    pub fn check_guards(&self) -> Try<()> {
+        let mut pass = Pass {
+            context: ErrCtx::NoContext,
+            errors: Vec::new(),
+            in_guard: false,
+        };
+
+        pass.visit_program(&self)?;
+
+        if pass.errors.len() > 0 {
+            return Err(pass.errors.into());
+        }
+
+        Ok(())
+    }
+}
+
+struct Pass {
+    context: ErrCtx,
+    errors: Vec<BuildErrWithCtx>,
+    in_guard: bool,
+}
+
+impl Pass {
+    fn visit_guard(&mut self, t: &Cond) -> Try<()> {
+        self.in_guard = true;
+        self.visit_cond(t)?;
+        self.in_guard = false;
+        Ok(())
+    }
+
+    fn forbid(&mut self, t: Expr) {
+        if self.in_guard {
+            self.errors.push(BuildErrWithCtx({
+                BuildErr::ImpureGuard(t)
+            }, self.context.clone()));
+        }
+    }
+}
+
+impl Visitor for Pass {
+    fn error_context(&mut self) -> &mut ErrCtx {
+        &mut self.context
+    }
+
+    fn visit_trap_arm(&mut self, t: &TrapArm) -> Try<()> {
+        self.visit_pattern(&t.pattern)?;
+        self.visit_pattern(&t.origin)?;
+        self.visit_guard(&t.guard)?;
+        self.visit_block(&t.body)
+    }
+
+    fn visit_weave_arm(&mut self, t: &WeaveArm) -> Try<()> {
+        self.visit_guard(&t.guard)?;
+        self.visit_expr(&t.message)?;
+        if let Some(ref reason) = t.locked {
+            self.visit_expr(reason)?;
+        }
+        if let Some(ref seconds) = t.timeout {
+            self.visit_expr(seconds)?;
+        }
+        self.visit_block(&t.body)
+    }
+
+    fn visit_builtin(&mut self, t: &Builtin, args: &[Expr]) -> Try<()> {
+        if !t.is_pure() {
+            self.forbid(Expr::Builtin(*t, args.to_vec()));
+        }
+
+        each(args, |t| self.visit_expr(t))
+    }
+
+    fn visit_call(&mut self, t: &Call) -> Try<()> {
+        // Only spawning can call a scene from inside an expression
+        self.forbid(Expr::Spawn(t.clone()));
+
+        let &Call(_, ref args) = t;
+        each(args, |t| self.visit_expr(t))
+    }
+
+    fn visit_string(&mut self, t: &Str) -> Try<()> {
+        // Each use of a variation moves it on to the next alternative
+        if let &Str::Varied(_) = t {
+            self.forbid(Expr::Str(t.clone()));
+        }
+
+        Ok(())
+    }
+}
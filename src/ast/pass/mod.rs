@@ -1,5 +1,12 @@
 pub mod argument_count;
+pub mod expand_macros;
+pub mod guard_purity;
+pub mod module_imports;
+pub mod pattern_analysis;
 pub mod prelude_restrictions;
+pub mod section_labels;
+pub mod string_table;
+pub mod type_inference;
 pub mod variable_definitions;
 
 pub mod qualify_modpaths;
@@ -0,0 +1,91 @@
+use std::collections::{HashMap, HashSet};
+
+use ast::*;
+use ast::rewrite::*;
+
+use driver::{Try, BuildErr, ErrCtx};
+
+impl Program {
+    /// Replaces the aliases made by `import` with the module paths they
+    /// stand for, so that later passes only see full paths.
+    pub fn resolve_imports(self) -> Try<Self> {
+        let known = self.modules.iter()
+            .map(|&(ref modpath, _)| modpath.clone())
+            .collect::<HashSet<_>>();
+
+        let mut errors = vec![];
+        let mut modules = Vec::with_capacity(self.modules.len());
+
+        for (modpath, module) in self.modules.into_iter() {
+            let mut context = ErrCtx::NoContext;
+            context.begin_module(&modpath);
+
+            let mut pass = Pass {
+                aliases: HashMap::new(),
+            };
+
+            for import in module.imports.iter() {
+                if !known.contains(&import.path) {
+                    errors.push({
+                        BuildErr::NoSuchModule(import.path.clone())
+                            .with_ctx(&context)
+                    });
+                }
+
+                let alias = import.alias.clone();
+                if pass.aliases.insert(alias, import.path.clone()).is_some() {
+                    errors.push({
+                        BuildErr::AliasRedefined(import.alias.clone())
+                            .with_ctx(&context)
+                    });
+                }
+            }
+
+            modules.push((modpath, Module {
+                globals: pass.rw_block(module.globals)?,
+                scenes: each(module.scenes, |t| pass.rw_scene(t))?,
+                imports: module.imports,
+                exports: module.exports,
+                macros: module.macros,
+            }));
+        }
+
+        if errors.len() > 0 {
+            return Err(errors.into());
+        }
+
+        Ok(Program {
+            modules: modules,
+        })
+    }
+}
+
+struct Pass {
+    aliases: HashMap<String, Modpath>,
+}
+
+impl Rewriter for Pass {
+    fn rw_scene_name(&mut self, t: SceneName) -> Try<SceneName> {
+        let SceneName { name, in_module } = t;
+
+        let in_module = in_module.map(|Modpath(segments)| {
+            let full_path = segments.first()
+                .and_then(|first| self.aliases.get(first));
+
+            match full_path {
+                Some(&Modpath(ref prefix)) => Modpath({
+                    prefix.iter().chain(segments.iter().skip(1))
+                        .cloned()
+                        .collect()
+                }),
+
+                None => Modpath(segments),
+            }
+        });
+
+        Ok(SceneName {
+            name: name,
+            in_module: in_module,
+        })
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+
+use ast::*;
+use ast::visit::Visitor;
+
+use driver::{Try, BuildErr, BuildErrWithCtx, ErrCtx};
+
+impl Program {
+    /// Looks for trap, listen and match arms that can never run, because an
+    /// earlier arm takes every value they would, and for matches which only
+    /// handle some of the atoms or list shapes they could be given, and have
+    /// nothing to fall back on.
+    ///
+    /// None of these stop the program from compiling, so they're returned
+    /// as warnings instead of errors.
+    pub fn check_patterns(&self) -> Try<Vec<BuildErrWithCtx>> {
+        let mut pass = Pass {
+            context: ErrCtx::NoContext,
+            warnings: vec![],
+            bindings: HashMap::new(),
+            declared: HashMap::new(),
+        };
+
+        for &(ref modpath, ref module) in self.modules.iter() {
+            pass.context.begin_module(modpath);
+
+            let mut bindings = Bindings::new(&pass.context);
+            bindings.visit_block(&module.globals)?;
+            pass.begin(bindings, &module.globals);
+            pass.check_block(&module.globals)?;
+
+            for scene in module.scenes.iter() {
+                let mut bindings = Bindings::new(&pass.context);
+                bindings.visit_scene(scene)?;
+                pass.begin(bindings, &scene.body);
+
+                pass.context.begin_scene(&scene.name.name)?;
+                pass.check_block(&scene.body)?;
+                pass.context.pop()?;
+            }
+        }
+
+        Ok(pass.warnings)
+    }
+}
+
+struct Pass {
+    context: ErrCtx,
+    warnings: Vec<BuildErrWithCtx>,
+
+    /// How many times each variable in the current scene is bound.
+    bindings: HashMap<Ident, usize>,
+
+    /// The atoms each variable in the current scene is set to by a `let`.
+    declared: HashMap<Ident, Vec<Atom>>,
+}
+
+impl Pass {
+    /// Forgets the variables of the last scene, and learns those of the next.
+    fn begin(&mut self, bindings: Bindings, body: &Block) {
+        self.bindings = bindings.counts;
+        self.declared.clear();
+        declare_atoms(body, &mut self.declared);
+    }
+
+    /// The only atoms a value can be, if they're known. That's when it's an
+    /// atom itself, or a variable which is only ever set to atoms.
+    fn closed_atoms(&self, value: &Expr) -> Option<Vec<Atom>> {
+        match value {
+            &Expr::Atom(ref atom) => Some(vec![atom.clone()]),
+
+            &Expr::Id(ref id) => {
+                let atoms = self.declared.get(id)?;
+
+                if self.bindings.get(id) == Some(&atoms.len()) {
+                    Some(atoms.clone())
+                } else {
+                    None
+                }
+            },
+
+            _ => None,
+        }
+    }
+
+    fn warn(&mut self, err: BuildErr) {
+        self.warnings.push(err.with_ctx(&self.context));
+    }
+
+    fn check_block(&mut self, t: &Block) -> Try<()> {
+        for stmt in t.0.iter() {
+            self.context.push_stmt(stmt)?;
+            self.check_stmt(stmt)?;
+            self.context.pop()?;
+        }
+
+        Ok(())
+    }
+
+    fn check_stmt(&mut self, t: &Stmt) -> Try<()> {
+        match t {
+            &Stmt::Trap { ref arms, .. } | &Stmt::Listen { ref arms, .. } => {
+                // The sender is matched along with the message
+                let patterns = arms.iter().map(|arm| {
+                    let pattern = Pat::List(vec![
+                        arm.pattern.clone(),
+                        arm.origin.clone(),
+                    ]);
+
+                    (pattern, &arm.guard)
+                }).collect::<Vec<_>>();
+
+                self.check_reachable(&patterns);
+
+                for arm in arms.iter() {
+                    self.check_block(&arm.body)?;
+                }
+            },
+
+            &Stmt::Match { ref value, ref arms, ref or_else } => {
+                let patterns = arms.iter().map(|arm| {
+                    (arm.pattern.clone(), &arm.guard)
+                }).collect::<Vec<_>>();
+
+                self.check_reachable(&patterns);
+
+                if or_else.0.is_empty() {
+                    let atoms = self.closed_atoms(value);
+
+                    if let Some(missing) = missing_value(&patterns, atoms.as_ref().map(|t| &t[..])) {
+                        self.warn(BuildErr::NonExhaustiveMatch {
+                            missing: missing,
+                        });
+                    }
+                }
+
+                for arm in arms.iter() {
+                    self.check_block(&arm.body)?;
+                }
+
+                self.check_block(or_else)?;
+            },
+
+            &Stmt::Weave { ref arms, .. } => {
+                for arm in arms.iter() {
+                    self.check_block(&arm.body)?;
+                }
+            },
+
+            &Stmt::If { ref success, ref failure, .. } => {
+                self.check_block(success)?;
+                self.check_block(failure)?;
+            },
+
+            &Stmt::Expanded { ref body, .. } => self.check_block(body)?,
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn check_reachable(&mut self, arms: &[(Pat, &Cond)]) {
+        for (i, &(ref later, _)) in arms.iter().enumerate() {
+            let earlier = arms[..i].iter().position(|&(ref pattern, guard)| {
+                guard == &Cond::True && covers(pattern, later)
+            });
+
+            if let Some(j) = earlier {
+                self.warn(BuildErr::UnreachableArm {
+                    arm: i + 1,
+                    covered_by: j + 1,
+                });
+            }
+        }
+    }
+}
+
+/// Counts the bindings of each variable in a scene.
+struct Bindings {
+    context: ErrCtx,
+    counts: HashMap<Ident, usize>,
+}
+
+impl Bindings {
+    fn new(context: &ErrCtx) -> Self {
+        Bindings {
+            context: context.clone(),
+            counts: HashMap::new(),
+        }
+    }
+}
+
+impl Visitor for Bindings {
+    fn error_context(&mut self) -> &mut ErrCtx {
+        &mut self.context
+    }
+
+    fn visit_id_assign(&mut self, t: &Ident) -> Try<()> {
+        *self.counts.entry(t.clone()).or_insert(0) += 1;
+        Ok(())
+    }
+}
+
+/// Collects the atoms which `let` sets each variable to.
+fn declare_atoms(t: &Block, declared: &mut HashMap<Ident, Vec<Atom>>) {
+    for stmt in t.0.iter() {
+        match stmt {
+            &Stmt::Let { ref name, value: Expr::Atom(ref atom) } => {
+                declared.entry(name.clone()).or_insert(vec![]).push(atom.clone());
+            },
+
+            &Stmt::Trap { ref arms, .. } | &Stmt::Listen { ref arms, .. } => {
+                for arm in arms.iter() {
+                    declare_atoms(&arm.body, declared);
+                }
+            },
+
+            &Stmt::Match { ref arms, ref or_else, .. } => {
+                for arm in arms.iter() {
+                    declare_atoms(&arm.body, declared);
+                }
+
+                declare_atoms(or_else, declared);
+            },
+
+            &Stmt::Weave { ref arms, .. } => {
+                for arm in arms.iter() {
+                    declare_atoms(&arm.body, declared);
+                }
+            },
+
+            &Stmt::If { ref success, ref failure, .. } => {
+                declare_atoms(success, declared);
+                declare_atoms(failure, declared);
+            },
+
+            &Stmt::Expanded { ref body, .. } => declare_atoms(body, declared),
+
+            _ => (),
+        }
+    }
+}
+
+/// Whether every value that matches `b` also matches `a`.
+fn covers(a: &Pat, b: &Pat) -> bool {
+    match (a, b) {
+        (&Pat::Hole, _) | (&Pat::Assign(_), _) => true,
+
+        (_, &Pat::Typed(ref b, _)) if covers(a, b) => true,
+
+        (&Pat::Typed(ref a, ty), b) => type_of(b) == Some(ty) && covers(a, b),
+
+        (&Pat::Match(ref a), &Pat::Match(ref b)) => a == b,
+
+        (&Pat::List(ref a), &Pat::List(ref b)) => {
+            a.len() == b.len() && covers_each(a, b)
+        },
+
+        (&Pat::Cons(ref heads, ref tail), &Pat::List(ref b)) => {
+            b.len() >= heads.len()
+                && covers_each(heads, b)
+                && covers(tail, &Pat::List(b[heads.len()..].to_vec()))
+        },
+
+        (&Pat::Cons(ref heads, ref tail), &Pat::Cons(ref b, ref rest)) => {
+            let rest = if b.len() == heads.len() {
+                (**rest).clone()
+            } else {
+                Pat::Cons(b[heads.len()..].to_vec(), rest.clone())
+            };
+
+            b.len() >= heads.len()
+                && covers_each(heads, b)
+                && covers(tail, &rest)
+        },
+
+        (&Pat::Map(ref a), &Pat::Map(ref b)) => {
+            a.iter().all(|&(ref key, ref a)| {
+                b.iter().any(|&(ref other, ref b)| key == other && covers(a, b))
+            })
+        },
+
+        _ => false,
+    }
+}
+
+/// Compares the leading items of two list patterns.
+fn covers_each(a: &[Pat], b: &[Pat]) -> bool {
+    a.iter().zip(b.iter()).all(|(a, b)| covers(a, b))
+}
+
+fn matches_anything(t: &Pat) -> bool {
+    covers(t, &Pat::Hole)
+}
+
+/// The type of every value a pattern matches, if there's only one.
+fn type_of(t: &Pat) -> Option<Type> {
+    match t {
+        &Pat::Typed(_, ty) => Some(ty),
+        &Pat::List(_) | &Pat::Cons(..) => Some(Type::List),
+        &Pat::Map(_) => Some(Type::Map),
+
+        &Pat::Match(ref literal) => match literal {
+            &Expr::Int(_) => Some(Type::Int),
+            &Expr::Atom(_) => Some(Type::Atom),
+            &Expr::Str(_) => Some(Type::Str),
+            &Expr::Bool(_) => Some(Type::Bool),
+            &Expr::PidOfSelf => Some(Type::Actor),
+            _ => None,
+        },
+
+        _ => None,
+    }
+}
+
+/// Finds a value which no arm would match, when every arm matches one of a
+/// known set of atoms or when every arm matches a list. Other matches are left
+/// alone, since there's no telling which values they'll really be given.
+fn missing_value(arms: &[(Pat, &Cond)], atoms: Option<&[Atom]>) -> Option<Pat> {
+    let patterns = arms.iter().map(|&(ref pattern, _)| pattern);
+
+    // Guarded arms still say what the match is over, but don't cover it
+    let unguarded = arms.iter()
+        .filter(|&&(_, guard)| guard == &Cond::True)
+        .map(|&(ref pattern, _)| pattern)
+        .collect::<Vec<_>>();
+
+    if arms.is_empty() || unguarded.iter().any(|t| matches_anything(t)) {
+        return None;
+    }
+
+    if patterns.clone().all(|t| type_of(t) == Some(Type::Atom)) {
+        return atoms.unwrap_or(&[]).iter()
+            .map(|atom| Pat::Match(Expr::Atom(atom.clone())))
+            .find(|atom| !unguarded.iter().any(|t| covers(t, atom)));
+    }
+
+    if !patterns.clone().all(|t| type_of(t) == Some(Type::List)) {
+        return None;
+    }
+
+    // Lengths of list which the arms take all of
+    let mut exact = vec![];
+    let mut at_least = None;
+
+    for pattern in unguarded.into_iter() {
+        match pattern {
+            &Pat::List(ref items) if items.iter().all(matches_anything) => {
+                exact.push(items.len());
+            },
+
+            &Pat::Cons(ref heads, ref tail)
+                if heads.iter().all(matches_anything) && matches_anything(tail) =>
+            {
+                at_least = Some(at_least.map_or(heads.len(), |n| {
+                    ::std::cmp::min(n, heads.len())
+                }));
+            },
+
+            _ => (),
+        }
+    }
+
+    // Without a list of some length or more, there's always a longer one
+    let bound = at_least.unwrap_or(exact.len() + 1);
+
+    (0..bound).find(|len| !exact.contains(len)).map(|len| {
+        Pat::List(vec![Pat::Hole; len])
+    })
+}
+
+#[test]
+fn warn_about_unreachable_arms() {
+    let source = "== start\n\ntrap\n| #hit, N\n    wait 0\n| #hit, 3\n    wait 0\n| #hit, N when N == 2\n    wait 0\n| #miss, N\n    wait 0\n;;\n";
+    let module = Module::parse(source).unwrap();
+
+    let program = Program {
+        modules: vec![
+            (Modpath(vec!["test".to_owned()]), module),
+        ],
+    };
+
+    let warnings = program.check_patterns().unwrap();
+    let arms = warnings.iter().map(|warning| match warning.0 {
+        BuildErr::UnreachableArm { arm, covered_by } => (arm, covered_by),
+        ref other => panic!("Unexpected warning {:?}", other),
+    }).collect::<Vec<_>>();
+
+    assert_eq!(arms, vec![(2, 1), (3, 1)]);
+}
+
+#[test]
+fn warn_about_missing_list_shapes() {
+    let shapes = |arms: Vec<Pat>| {
+        let arms = arms.into_iter()
+            .map(|pattern| (pattern, &Cond::True))
+            .collect::<Vec<_>>();
+
+        missing_value(&arms, None)
+    };
+
+    let hole = || Pat::Hole;
+    let rest = || Box::new(Pat::Hole);
+
+    assert_eq!(shapes(vec![
+        Pat::List(vec![]),
+        Pat::Cons(vec![hole()], rest()),
+    ]), None);
+
+    assert_eq!(shapes(vec![
+        Pat::List(vec![]),
+        Pat::Cons(vec![hole(), hole()], rest()),
+    ]), Some(Pat::List(vec![hole()])));
+
+    assert_eq!(shapes(vec![
+        Pat::List(vec![]),
+        Pat::List(vec![hole()]),
+    ]), Some(Pat::List(vec![hole(), hole()])));
+}
+
+#[test]
+fn warn_about_non_exhaustive_matches() {
+    let source = "== start(Given)\n\nlet Mood = #happy\nlet Mood = #sad\n\nmatch Mood\n| #happy\n    trace 1\n;;\n\nmatch Given\n| #happy\n    trace 2\n;;\n\nmatch [1]\n| []\n    trace 3\n| [_, _ | _]\n    trace 4\n;;\n";
+    let module = Module::parse(source).unwrap();
+
+    let program = Program {
+        modules: vec![
+            (Modpath(vec!["test".to_owned()]), module),
+        ],
+    };
+
+    let warnings = program.check_patterns().unwrap();
+    let missing = warnings.iter().map(|warning| match warning.0 {
+        BuildErr::NonExhaustiveMatch { ref missing } => missing.clone(),
+        ref other => panic!("Unexpected warning {:?}", other),
+    }).collect::<Vec<_>>();
+
+    // Given could be any atom, so there's no telling what's missing
+    assert_eq!(missing, vec![
+        Pat::Match(Expr::Atom(Atom::User("sad".to_owned()))),
+        Pat::List(vec![Pat::Hole]),
+    ]);
+}
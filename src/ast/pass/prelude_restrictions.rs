@@ -53,6 +53,29 @@ impl Visitor for Pass {
             }, self.context.clone()));
         }
 
+        if let &Expr::Global(ref name) = t {
+            self.visit_global(name)?;
+        }
+
+        // Choosing an alternative means keeping count of which came before
+        if let &Expr::Str(Str::Varied(_)) = t {
+            self.errors.push(BuildErrWithCtx({
+                BuildErr::IoInPrelude
+            }, self.context.clone()));
+        }
+
+        Ok(())
+    }
+
+    fn visit_global(&mut self, _t: &str) -> Try<()> {
+        if let &ErrCtx::Local(_, _) = &self.context {
+            return Ok(());
+        }
+
+        self.errors.push(BuildErrWithCtx({
+            BuildErr::IoInPrelude
+        }, self.context.clone()));
+
         Ok(())
     }
 }
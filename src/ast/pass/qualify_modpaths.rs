@@ -46,6 +46,7 @@ impl Scene {
                 in_module: Some(modpath.clone()),
             },
             args: self.args,
+            arg_types: self.arg_types,
             body: pass.rw_block(self.body)?,
         })
     }
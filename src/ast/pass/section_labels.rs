@@ -0,0 +1,133 @@
+use ast::*;
+use ast::visit::*;
+
+use driver::{Try, ErrCtx, BuildErr, BuildErrWithCtx};
+
+impl Program {
+    pub fn check_sections(&self) -> Try<()> {
+        let mut pass = Pass {
+            context: ErrCtx::NoContext,
+            errors: Vec::new(),
+            regions: Vec::new(),
+        };
+
+        pass.visit_program(self)?;
+
+        if pass.errors.len() > 0 {
+            return Err(pass.errors.into());
+        }
+
+        Ok(())
+    }
+}
+
+struct Pass {
+    context: ErrCtx,
+    errors: Vec<BuildErrWithCtx>,
+
+    /// Sections which can be diverted to from the code being checked.
+    regions: Vec<Vec<Label>>,
+}
+
+impl Pass {
+    fn push_err(&mut self, err: BuildErr) {
+        self.errors.push(BuildErrWithCtx(err, self.context.clone()));
+    }
+}
+
+/// Collects the sections defined in a block. Trap arms don't run inline with
+/// the rest of the scene, so their sections are only included on request.
+fn sections_in(t: &Block, with_traps: bool, found: &mut Vec<Label>) {
+    for stmt in t.0.iter() {
+        match stmt {
+            &Stmt::Section { ref name } => found.push(name.clone()),
+
+            &Stmt::Expanded { ref body, .. } => {
+                sections_in(body, with_traps, found);
+            },
+
+            &Stmt::If { ref success, ref failure, .. } => {
+                sections_in(success, with_traps, found);
+                sections_in(failure, with_traps, found);
+            },
+
+            &Stmt::Match { ref arms, ref or_else, .. } => {
+                for arm in arms.iter() {
+                    sections_in(&arm.body, with_traps, found);
+                }
+
+                sections_in(or_else, with_traps, found);
+            },
+
+            &Stmt::Weave { ref arms, .. } => {
+                for arm in arms.iter() {
+                    sections_in(&arm.body, with_traps, found);
+                }
+            },
+
+            &Stmt::Trap { ref arms, .. } | &Stmt::Listen { ref arms, .. } => {
+                if with_traps {
+                    for arm in arms.iter() {
+                        sections_in(&arm.body, with_traps, found);
+                    }
+                }
+            },
+
+            _ => (),
+        }
+    }
+}
+
+impl Visitor for Pass {
+    fn error_context(&mut self) -> &mut ErrCtx {
+        &mut self.context
+    }
+
+    fn visit_scene(&mut self, t: &Scene) -> Try<()> {
+        self.error_context().begin_scene(&t.name.name)?;
+
+        let mut all = vec![];
+        sections_in(&t.body, true, &mut all);
+
+        for (i, name) in all.iter().enumerate() {
+            if all[.. i].contains(name) {
+                self.push_err(BuildErr::LabelRedefined(name.clone()));
+            }
+        }
+
+        let mut region = vec![];
+        sections_in(&t.body, false, &mut region);
+
+        self.regions.push(region);
+        self.visit_block(&t.body)?;
+        self.regions.pop();
+
+        self.error_context().pop()
+    }
+
+    fn visit_trap_arm(&mut self, t: &TrapArm) -> Try<()> {
+        let mut region = vec![];
+        sections_in(&t.body, false, &mut region);
+
+        self.regions.push(region);
+        self.visit_block(&t.body)?;
+        self.regions.pop();
+
+        Ok(())
+    }
+
+    fn visit_divert(&mut self, t: &Label) -> Try<()> {
+        let found = match self.regions.last() {
+            Some(region) => region.contains(t),
+
+            // Reported by check_prelude_restrictions
+            None => return Ok(()),
+        };
+
+        if !found {
+            self.push_err(BuildErr::NoSuchLabel(t.clone()));
+        }
+
+        Ok(())
+    }
+}
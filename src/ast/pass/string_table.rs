@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use ast::*;
+use ast::pass::*;
+use ast::visit::*;
+
+use driver::{Try, ErrCtx};
+
+use locale::Entry;
+
+use vm::string_id;
+
+impl DesugaredProgram {
+    /// Lists every string constant the program will use, in the order they
+    /// first appear, along with the scenes that use them.
+    pub fn strings(&self) -> Try<Vec<Entry>> {
+        let mut pass = Pass {
+            context: ErrCtx::NoContext,
+            scene: String::new(),
+            entries: Vec::new(),
+            index: HashMap::new(),
+        };
+
+        for &(ref modpath, ref body) in self.preludes.iter() {
+            pass.context.begin_module(modpath);
+            pass.scene = format!("{}", modpath);
+            pass.visit_block(body)?;
+        }
+
+        for scene in self.scenes.iter() {
+            let modpath = match scene.name.in_module.as_ref() {
+                Some(modpath) => modpath,
+                None => ice!("Unqualified scene name: {}", scene.name),
+            };
+
+            pass.context.begin_module(modpath);
+            pass.scene = format!("{}", scene.name);
+            pass.visit_block(&scene.body)?;
+        }
+
+        for lambda in self.lambdas.iter() {
+            let in_scene = match &lambda.label {
+                &Label::Qualified(ref label) => &label.in_scene,
+                other => ice!("Unqualified trap label: {}", other),
+            };
+
+            pass.context.begin_module(&in_scene.in_module);
+            pass.scene = format!("{}", in_scene);
+            pass.visit_block(&lambda.body)?;
+        }
+
+        Ok(pass.entries)
+    }
+}
+
+struct Pass {
+    context: ErrCtx,
+
+    /// Where the strings being collected are used.
+    scene: String,
+
+    entries: Vec<Entry>,
+    index: HashMap<String, usize>,
+}
+
+impl Pass {
+    fn add(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        if let Some(&i) = self.index.get(text) {
+            let entry = &mut self.entries[i];
+
+            if !entry.contexts.contains(&self.scene) {
+                entry.contexts.push(self.scene.clone());
+            }
+
+            return;
+        }
+
+        self.index.insert(text.to_owned(), self.entries.len());
+
+        self.entries.push(Entry {
+            id: string_id(text),
+            text: text.to_owned(),
+            contexts: vec![self.scene.clone()],
+        });
+    }
+}
+
+impl Visitor for Pass {
+    fn error_context(&mut self) -> &mut ErrCtx {
+        &mut self.context
+    }
+
+    fn visit_string(&mut self, t: &Str) -> Try<()> {
+        // Must match the way ast::translate interns strings
+        match t {
+            &Str::Plain(ref text) => self.add(text),
+            other => self.add(&format!("{}", other)),
+        }
+
+        Ok(())
+    }
+
+    fn visit_vary(&mut self, t: &VarySite) -> Try<()> {
+        for item in t.items.iter() {
+            self.add(item);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+
+use ast::*;
+use ast::pass::*;
+use ast::visit::Visitor;
+
+use driver::{Try, BuildErr, BuildErrWithCtx, ErrCtx};
+
+impl DesugaredProgram {
+    /// Works out what type of value each expression has, where that can be
+    /// known before the program runs, and reports values used as the wrong
+    /// type. Types come from literals, built-ins, annotated scene arguments,
+    /// and annotated patterns like `Count: int`. Anything else is left
+    /// unchecked.
+    pub fn check_types(&self) -> Try<()> {
+        let mut pass = Pass {
+            signatures: HashMap::new(),
+            context: ErrCtx::NoContext,
+            errors: vec![],
+            vars: HashMap::new(),
+            facts: vec![],
+            reassigned: HashMap::new(),
+        };
+
+        for scene in self.scenes.iter() {
+            let name = scene.name.qualified()?;
+            pass.signatures.insert(name, scene.arg_types.clone());
+        }
+
+        for &(ref modpath, ref body) in self.preludes.iter() {
+            pass.context.begin_module(modpath);
+            pass.begin(body)?;
+            pass.check_block(body)?;
+        }
+
+        for scene in self.scenes.iter() {
+            pass.context = ErrCtx::Local(scene.name.qualified()?, vec![]);
+            pass.begin(&scene.body)?;
+
+            for (arg, ty) in scene.args.iter().zip(scene.arg_types.iter()) {
+                if let (&Some(ref arg), &Some(ty)) = (arg, ty) {
+                    pass.vars.insert(arg.clone(), ty);
+                }
+            }
+
+            pass.check_block(&scene.body)?;
+        }
+
+        for lambda in self.lambdas.iter() {
+            let in_scene = lambda.label.qualified()?.in_scene;
+            pass.context = ErrCtx::Local(in_scene, vec![]);
+            pass.begin(&lambda.body)?;
+            pass.check_block(&lambda.body)?;
+        }
+
+        if pass.errors.len() > 0 {
+            return Err(pass.errors.into());
+        }
+
+        Ok(())
+    }
+}
+
+type Vars = HashMap<Ident, Type>;
+
+struct Pass {
+    /// Declared argument types of every scene.
+    signatures: HashMap<QfdSceneName, Vec<Option<Type>>>,
+
+    context: ErrCtx,
+    errors: Vec<BuildErrWithCtx>,
+
+    /// Types of the variables known at this point in the scene.
+    vars: Vars,
+
+    /// Types of values already tested by the conditions around this point,
+    /// such as the parts of a message matched by `Count: int`.
+    facts: Vec<(Expr, Type)>,
+
+    /// How many times each variable in the scene is assigned to.
+    reassigned: HashMap<Ident, usize>,
+}
+
+impl Pass {
+    fn begin(&mut self, body: &Block) -> Try<()> {
+        self.vars.clear();
+        self.facts.clear();
+
+        let mut counter = Assignments {
+            context: self.context.clone(),
+            counts: HashMap::new(),
+        };
+
+        counter.visit_block(body)?;
+        self.reassigned = counter.counts;
+
+        Ok(())
+    }
+
+    fn push_err(&mut self, err: BuildErr) {
+        self.errors.push(err.with_ctx(&self.context));
+    }
+
+    fn mismatch(&mut self, expected: Type, found: Type) {
+        self.push_err(BuildErr::TypeMismatch {
+            expected: expected,
+            found: found,
+        });
+    }
+
+    fn expect(&mut self, t: &Expr, expected: Type) -> Try<()> {
+        match self.infer(t)? {
+            Some(found) if found != expected => self.mismatch(expected, found),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn infer(&mut self, t: &Expr) -> Try<Option<Type>> {
+        if let Some(&(_, ty)) = self.facts.iter().rev().find(|f| &f.0 == t) {
+            return Ok(Some(ty));
+        }
+
+        Ok(match t {
+            &Expr::Arg(_) => None,
+            &Expr::Atom(_) => Some(Type::Atom),
+            &Expr::Global(_) => None,
+            &Expr::Int(_) => Some(Type::Int),
+            &Expr::Str(_) => Some(Type::Str),
+            &Expr::Vary(_) => Some(Type::Str),
+            &Expr::Visits(_) => Some(Type::Int),
+            &Expr::PidOfSelf => Some(Type::Actor),
+            &Expr::PidZero => Some(Type::Actor),
+            &Expr::Infinity => None,
+
+            &Expr::Id(ref id) => self.vars.get(id).cloned(),
+
+            &Expr::Bool(ref cond) => {
+                self.check_cond(cond)?;
+                Some(Type::Bool)
+            },
+
+            &Expr::Group(ref name) => {
+                self.expect(name, Type::Atom)?;
+                None
+            },
+
+            &Expr::Format(ref template, ref args) => {
+                self.infer(template)?;
+                self.infer_each(args)?;
+                Some(Type::Str)
+            },
+
+            &Expr::Op(_, ref args) => {
+                for arg in args.iter() {
+                    self.expect(arg, Type::Int)?;
+                }
+
+                Some(Type::Int)
+            },
+
+            &Expr::List(ref items) => {
+                self.infer_each(items)?;
+                Some(Type::List)
+            },
+
+            &Expr::Splice(ref items) | &Expr::MenuChoice(ref items) => {
+                self.infer_each(items)?;
+                None
+            },
+
+            // Patterns take apart values of any type; they test first
+            &Expr::Nth(ref list, _) => {
+                self.infer(list)?;
+                None
+            },
+
+            &Expr::Drop(ref list, _) => {
+                self.infer(list)?;
+                Some(Type::List)
+            },
+
+            &Expr::TryField(ref map, _) => {
+                self.infer(map)?;
+                None
+            },
+
+            &Expr::Index(ref list, ref index) => {
+                self.expect(list, Type::List)?;
+                self.expect(index, Type::Int)?;
+                None
+            },
+
+            &Expr::Builtin(builtin, ref args) => self.infer_builtin(builtin, args)?,
+
+            &Expr::Map(ref entries) => {
+                for &(_, ref value) in entries.iter() {
+                    self.infer(value)?;
+                }
+
+                Some(Type::Map)
+            },
+
+            &Expr::Update(ref map, ref entries) => {
+                self.expect(map, Type::Map)?;
+
+                for &(_, ref value) in entries.iter() {
+                    self.infer(value)?;
+                }
+
+                Some(Type::Map)
+            },
+
+            &Expr::Field(ref map, _) => {
+                self.expect(map, Type::Map)?;
+                None
+            },
+
+            &Expr::Spawn(ref call) => {
+                self.check_call(call)?;
+                Some(Type::Actor)
+            },
+        })
+    }
+
+    fn infer_each(&mut self, t: &[Expr]) -> Try<()> {
+        for expr in t.iter() {
+            self.infer(expr)?;
+        }
+
+        Ok(())
+    }
+
+    fn infer_builtin(&mut self, t: Builtin, args: &[Expr]) -> Try<Option<Type>> {
+        let (wanted, result) = match t {
+            Builtin::Length => {
+                for arg in args.iter() {
+                    match self.infer(arg)? {
+                        Some(Type::List) | Some(Type::Str) | None => (),
+                        Some(found) => self.mismatch(Type::List, found),
+                    }
+                }
+
+                return Ok(Some(Type::Int));
+            },
+
+            Builtin::ToString => {
+                self.infer_each(args)?;
+                return Ok(Some(Type::Str));
+            },
+
+            Builtin::Member => {
+                if let Some(item) = args.first() {
+                    self.infer(item)?;
+                }
+
+                for list in args.iter().skip(1) {
+                    self.expect(list, Type::List)?;
+                }
+
+                return Ok(Some(Type::Bool));
+            },
+
+            Builtin::Append => (Type::List, Type::List),
+            Builtin::Concat => (Type::Str, Type::Str),
+            Builtin::Roll => (Type::Int, Type::Int),
+        };
+
+        for arg in args.iter() {
+            self.expect(arg, wanted)?;
+        }
+
+        Ok(Some(result))
+    }
+
+    fn check_cond(&mut self, t: &Cond) -> Try<()> {
+        match t {
+            &Cond::True | &Cond::False | &Cond::LastResort => (),
+
+            &Cond::HasLength(ref value, _)
+                | &Cond::MinLength(ref value, _)
+                | &Cond::HasKey(ref value, _)
+                | &Cond::HasType(ref value, _) =>
+            {
+                self.infer(value)?;
+            },
+
+            &Cond::IsTrue(ref value) => self.expect(value, Type::Bool)?,
+
+            &Cond::Compare(BoolOp::Eql, ref lhs, ref rhs) => {
+                // Bools and ints can't be compared at run time
+                match (self.infer(lhs)?, self.infer(rhs)?) {
+                    (Some(Type::Bool), Some(Type::Int)) => {
+                        self.mismatch(Type::Bool, Type::Int);
+                    },
+
+                    (Some(Type::Int), Some(Type::Bool)) => {
+                        self.mismatch(Type::Int, Type::Bool);
+                    },
+
+                    _ => (),
+                }
+            },
+
+            &Cond::Compare(BoolOp::Same, ref lhs, ref rhs) => {
+                self.infer(lhs)?;
+                self.infer(rhs)?;
+            },
+
+            &Cond::Compare(_, ref lhs, ref rhs) => {
+                self.expect(lhs, Type::Int)?;
+                self.expect(rhs, Type::Int)?;
+            },
+
+            &Cond::And(ref conds) | &Cond::Or(ref conds) => {
+                for cond in conds.iter() {
+                    self.check_cond(cond)?;
+                }
+            },
+
+            &Cond::Not(ref cond) => self.check_cond(cond)?,
+        }
+
+        Ok(())
+    }
+
+    fn check_call(&mut self, t: &Call) -> Try<()> {
+        let &Call(ref name, ref args) = t;
+        let name = name.qualified()?;
+
+        let mut found = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            found.push(self.infer(arg)?);
+        }
+
+        let wanted = match self.signatures.get(&name) {
+            Some(wanted) => wanted.clone(),
+            None => return Ok(()),
+        };
+
+        for (i, (wanted, found)) in wanted.into_iter().zip(found).enumerate() {
+            match (wanted, found) {
+                (Some(expected), Some(found)) if expected != found => {
+                    self.push_err(BuildErr::ArgTypeMismatch {
+                        scene: name.clone(),
+                        arg: i,
+                        expected: expected,
+                        found: found,
+                    });
+                },
+
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_block(&mut self, t: &Block) -> Try<()> {
+        for stmt in t.0.iter() {
+            self.context.push_stmt(stmt)?;
+            self.check_stmt(stmt)?;
+            self.context.pop()?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks a block which may not run, and returns the variables as they
+    /// would be at the end of it.
+    fn check_branch(&mut self, t: &Block, facts: Vec<(Expr, Type)>) -> Try<Vars> {
+        let vars = self.vars.clone();
+        let depth = self.facts.len();
+
+        self.facts.extend(facts);
+        self.check_block(t)?;
+        self.facts.truncate(depth);
+
+        Ok(::std::mem::replace(&mut self.vars, vars))
+    }
+
+    /// Keeps only the variable types all of the branches agree on.
+    fn join(&mut self, branches: Vec<Vars>) {
+        let mut branches = branches.into_iter();
+
+        let mut vars = match branches.next() {
+            Some(vars) => vars,
+            None => return,
+        };
+
+        for other in branches {
+            vars.retain(|id, ty| other.get(id) == Some(ty));
+        }
+
+        self.vars = vars;
+    }
+
+    fn check_stmt(&mut self, t: &Stmt) -> Try<()> {
+        match t {
+            &Stmt::Let { ref name, ref value } => {
+                match self.infer(value)? {
+                    Some(ty) => self.vars.insert(name.clone(), ty),
+                    None => self.vars.remove(name),
+                };
+            },
+
+            &Stmt::Discard { ref value } | &Stmt::Trace { ref value } => {
+                self.infer(value)?;
+            },
+
+            &Stmt::SetGlobal { ref value, .. } => {
+                self.infer(value)?;
+            },
+
+            &Stmt::Say { ref message, .. } => {
+                self.infer(message)?;
+            },
+
+            &Stmt::Arm { ref with_env, .. } => {
+                self.infer(with_env)?;
+            },
+
+            &Stmt::Wait { ref value } => self.expect(value, Type::Int)?,
+
+            &Stmt::Register { ref name } => self.expect(name, Type::Atom)?,
+
+            &Stmt::Join { ref group } | &Stmt::Leave { ref group } => {
+                self.expect(group, Type::Atom)?;
+            },
+
+            &Stmt::SendMsg { ref target, ref message } => {
+                self.infer(message)?;
+
+                // Registered names and groups can stand in for actors
+                match self.infer(target)? {
+                    Some(Type::Actor) | Some(Type::Atom) | None => (),
+                    Some(found) => self.mismatch(Type::Actor, found),
+                }
+            },
+
+            &Stmt::Recur { ref target } | &Stmt::Tunnel { ref target } => {
+                self.check_call(target)?;
+            },
+
+            &Stmt::If { ref test, ref success, ref failure } => {
+                self.check_cond(test)?;
+
+                let mut facts = vec![];
+                facts_of(test, &mut facts);
+
+                let success = self.check_branch(success, facts)?;
+                let failure = self.check_branch(failure, vec![])?;
+                self.join(vec![success, failure]);
+            },
+
+            &Stmt::Match { ref value, ref arms, ref or_else } => {
+                self.infer(value)?;
+
+                let mut branches = vec![];
+                for arm in arms.iter() {
+                    self.check_cond(&arm.guard)?;
+                    branches.push(self.check_branch(&arm.body, vec![])?);
+                }
+
+                branches.push(self.check_branch(or_else, vec![])?);
+                self.join(branches);
+            },
+
+            &Stmt::Expanded { ref body, .. } => self.check_block(body)?,
+
+            // Diverting back here may bring values of another type along
+            &Stmt::Section { .. } => {
+                let reassigned = &self.reassigned;
+                self.vars.retain(|id, _| {
+                    reassigned.get(id).cloned().unwrap_or(0) < 2
+                });
+            },
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the types a condition proves, if it holds.
+fn facts_of(t: &Cond, facts: &mut Vec<(Expr, Type)>) {
+    match t {
+        &Cond::HasType(ref value, ty) => facts.push((value.clone(), ty)),
+
+        &Cond::HasLength(ref value, _) | &Cond::MinLength(ref value, _) => {
+            facts.push((value.clone(), Type::List));
+        },
+
+        &Cond::HasKey(ref value, _) => facts.push((value.clone(), Type::Map)),
+
+        &Cond::And(ref conds) => {
+            for cond in conds.iter() {
+                facts_of(cond, facts);
+            }
+        },
+
+        _ => (),
+    }
+}
+
+/// Counts assignments to each variable.
+struct Assignments {
+    context: ErrCtx,
+    counts: HashMap<Ident, usize>,
+}
+
+impl Visitor for Assignments {
+    fn error_context(&mut self) -> &mut ErrCtx {
+        &mut self.context
+    }
+
+    fn visit_id_assign(&mut self, t: &Ident) -> Try<()> {
+        *self.counts.entry(t.clone()).or_insert(0) += 1;
+        Ok(())
+    }
+}
@@ -34,6 +34,25 @@ impl Display for ast::Stmt {
                 write!(f, "disarm {}", target)
             },
 
+            &ast::Stmt::Expand { ref name, ref args } => {
+                write!(f, "?{}", name)?;
+
+                if args.is_empty() {
+                    return Ok(());
+                }
+
+                write!(f, "(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            },
+
+            &ast::Stmt::Expanded { ref name, ref defined_at, .. } => {
+                write!(f, "?{}, as defined on line {}", name, defined_at)
+            },
+
             &ast::Stmt::Let { ref name, ref value } => {
                 write!(f, "let {} = {}", name, value)
             },
@@ -46,7 +65,7 @@ impl Display for ast::Stmt {
                 writeln!(f, ";;")
             },
 
-            &ast::Stmt::Say { ref message } => {
+            &ast::Stmt::Say { ref message, .. } => {
                 writeln!(f, "say {}", message)
             },
 
@@ -117,7 +136,23 @@ impl Display for ast::Pat {
                     .map(|i| format!("{}", i))
                     .collect::<Vec<_>>()
                     .join(", ")
-            })
+            }),
+
+            &ast::Pat::Cons(ref items, ref rest) => write!(f, "[{} | {}]", {
+                items.iter()
+                    .map(|i| format!("{}", i))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }, rest),
+
+            &ast::Pat::Map(ref entries) => write!(f, "{{{}}}", {
+                entries.iter()
+                    .map(|&(ref key, ref pat)| format!("{}: {}", key, pat))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+
+            &ast::Pat::Typed(ref pat, ref ty) => write!(f, "{}: {}", pat, ty),
         }
     }
 }
@@ -147,6 +182,68 @@ impl Display for ast::QfdLabel {
     }
 }
 
+impl Display for ast::Str {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let fragments = match self {
+            &ast::Str::Plain(ref text) => return write!(f, "{}", text),
+            &ast::Str::Varied(ref fragments) => fragments,
+        };
+
+        for fragment in fragments.iter() {
+            match fragment {
+                &ast::Fragment::Text(ref text) => write!(f, "{}", text)?,
+                &ast::Fragment::Vary(ref variation) => {
+                    write!(f, "{}", variation)?
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ast::Variation {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let sigil = match self.kind {
+            ast::VariationKind::Sequence => "",
+            ast::VariationKind::Cycle => "&",
+            ast::VariationKind::Once => "!",
+            ast::VariationKind::Shuffle => "~",
+        };
+
+        let actor = if self.per_actor { "@" } else { "" };
+
+        write!(f, "{{{}{}{}}}", actor, sigil, self.items.join("|"))
+    }
+}
+
+impl Display for ast::Builtin {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            &ast::Builtin::Length => write!(f, "length"),
+            &ast::Builtin::Append => write!(f, "append"),
+            &ast::Builtin::Concat => write!(f, "concat"),
+            &ast::Builtin::ToString => write!(f, "to_string"),
+            &ast::Builtin::Roll => write!(f, "roll"),
+            &ast::Builtin::Member => write!(f, "member"),
+        }
+    }
+}
+
+impl Display for ast::Type {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            &ast::Type::Int => write!(f, "int"),
+            &ast::Type::Bool => write!(f, "bool"),
+            &ast::Type::Atom => write!(f, "atom"),
+            &ast::Type::Str => write!(f, "string"),
+            &ast::Type::List => write!(f, "list"),
+            &ast::Type::Actor => write!(f, "actor"),
+            &ast::Type::Map => write!(f, "map"),
+        }
+    }
+}
+
 impl Display for ast::Call {
     fn fmt(&self, f: &mut Formatter) -> Result {
         let &ast::Call(ref name, ref args) = self;
@@ -210,6 +307,14 @@ impl Display for TokErr {
             &ErrReason::InvalidScreamingCase => {
                 write!(f, "Macro names must be in SCREAMING_CASE")
             },
+
+            &ErrReason::NoSuchBuiltin => {
+                write!(f, "No built-in function has that name")
+            },
+
+            &ErrReason::NoSuchType => {
+                write!(f, "No type has that name")
+            },
         }
     }
 }
@@ -220,16 +325,29 @@ impl<'a> Display for Tok<'a> {
             &Tok::EndLn => ";",
             &Tok::EndBlk => ";;",
 
+            &Tok::KwAs => "as",
             &Tok::KwDisarm => "disarm",
+            &Tok::KwElse => "else",
+            &Tok::KwExport => "export",
+            &Tok::KwFalse => "false",
             &Tok::KwFrom => "from",
             &Tok::KwGiven => "given",
+            &Tok::KwGroup => "group",
             &Tok::KwIf => "if",
+            &Tok::KwImport => "import",
+            &Tok::KwJoin => "join",
+            &Tok::KwLeave => "leave",
             &Tok::KwLet => "let",
             &Tok::KwListen => "listen",
+            &Tok::KwMatch => "match",
+            &Tok::KwRegister => "register",
+            &Tok::KwSeen => "seen",
             &Tok::KwSpawn => "spawn",
             &Tok::KwThen => "then",
             &Tok::KwTrace => "trace",
             &Tok::KwTrap => "trap",
+            &Tok::KwTrue => "true",
+            &Tok::KwVisits => "visits",
             &Tok::KwWait => "wait",
             &Tok::KwWeave => "weave",
             &Tok::KwWhen => "when",
@@ -238,6 +356,7 @@ impl<'a> Display for Tok<'a> {
             &Tok::NmLabel(ref s) => s,
             &Tok::NmMacro(ref s) => s,
             &Tok::NmVar(ref s) => s,
+            &Tok::NmGlobal(ref s) => s,
 
             &Tok::LitAtom(ref s) => s,
             &Tok::LitInt(ref s) => s,
@@ -337,11 +456,69 @@ impl Display for BuildErrWithCtx {
                 write!(f, "The scene {:?} was not found in the module {}.", &name.name, name.in_module)?;
             },
 
+            &BuildErr::SceneIsPrivate(ref name) => {
+                write!(f, "The scene {:?} is not exported from the module {}.", &name.name, name.in_module)?;
+            },
+
+            &BuildErr::AliasRedefined(ref alias) => {
+                write!(f, "More than one module was imported as {:?}.", alias)?;
+            },
+
             &BuildErr::WrongNumberOfArgs { ref call, ref wanted, ref got } => {
                 writeln!(f, "In the expression:\n{}", call)?;
                 write!(f, "The function {} needs {} args, but was called with {}", &call.0.name, wanted, got)?;
             },
 
+            &BuildErr::WrongNumberOfBuiltinArgs { ref builtin, ref wanted, ref got } => {
+                write!(f, "The built-in {} needs {} args, but was called with {}", builtin, wanted, got)?;
+            },
+
+            &BuildErr::NoSuchMacro(ref name) => {
+                writeln!(f, "The macro ?{} was not defined in this module.", name)?;
+            },
+
+            &BuildErr::MacroRedefined(ref name) => {
+                writeln!(f, "The macro ?{} was defined more than once.", name)?;
+            },
+
+            &BuildErr::RecursiveMacro { ref name, ref defined_at } => {
+                writeln!(f, "The macro ?{} (defined on line {}) is used inside its own expansion.", name, defined_at)?;
+            },
+
+            &BuildErr::WrongNumberOfMacroArgs { ref name, ref defined_at, ref used_at, ref wanted, ref got } => {
+                writeln!(f, "The macro ?{} (defined on line {}) needs {} args, but was given {} on line {}", name, defined_at, wanted, got, used_at)?;
+            },
+
+            &BuildErr::VarOnlyInMacro { ref var, ref name, ref defined_at, ref expanded_at, ref used_at } => {
+                writeln!(f, "The variable {} is used on line {}, but it only exists inside the macro ?{} (defined on line {}, used on line {}).", var, used_at, name, defined_at, expanded_at)?;
+            },
+
+            &BuildErr::TypeMismatch { ref expected, ref found } => {
+                writeln!(f, "Expected a value of type {} here, but found {}.", expected, found)?;
+            },
+
+            &BuildErr::ArgTypeMismatch { ref scene, ref arg, ref expected, ref found } => {
+                writeln!(f, "Argument {} of {} should be of type {}, but was given {}.", arg + 1, scene, expected, found)?;
+            },
+
+            &BuildErr::ImpureGuard(ref expr) => {
+                let effect = match expr {
+                    &ast::Expr::Spawn(ref call) => format!("spawns {}", call),
+                    &ast::Expr::Builtin(builtin, _) => format!("calls {}", builtin),
+                    _ => "uses a string that varies".to_owned(),
+                };
+
+                writeln!(f, "Guards can't change anything, but this one {}.", effect)?;
+            },
+
+            &BuildErr::UnreachableArm { ref arm, ref covered_by } => {
+                writeln!(f, "Arm {} can never run, because arm {} above it takes everything it would.", arm, covered_by)?;
+            },
+
+            &BuildErr::NonExhaustiveMatch { ref missing } => {
+                writeln!(f, "No arm handles values like {}, and there is nothing to fall back on.", missing)?;
+            },
+
             &BuildErr::InvalidNumber(ref s) => {
                 write!(f, "The number {} could not be parsed", s)?;
             },
@@ -358,6 +535,14 @@ impl Display for BuildErrWithCtx {
                 writeln!(f, "Traps not allowed in module prelude")?;
             },
 
+            &BuildErr::NoSuchLabel(ref label) => {
+                write!(f, "The section {} was not found in this scene.", label)?;
+            },
+
+            &BuildErr::LabelRedefined(ref label) => {
+                write!(f, "The section {} was defined more than once.", label)?;
+            },
+
             e => write!(f, "Can't describe this error yet: {:?}", e)?,
         };
 
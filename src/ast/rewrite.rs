@@ -32,6 +32,7 @@ pub trait Rewriter {
                 Some(t) => Ok(Some(self.rw_id_assign(t)?)),
                 None => Ok(None),
             })?,
+            arg_types: t.arg_types,
             body: self.rw_block(t.body)?,
         })
     }
@@ -63,12 +64,35 @@ pub trait Rewriter {
                 value: self.rw_expr(value)?,
             },
 
+            Stmt::Divert { target } => Stmt::Divert {
+                target: self.rw_label(target)?,
+            },
+
+            Stmt::Expand { name, args } => Stmt::Expand {
+                name: name,
+                args: each(args, |t| self.rw_expr(t))?,
+            },
+
+            Stmt::Expanded { name, defined_at, body } => Stmt::Expanded {
+                name: name,
+                defined_at: defined_at,
+                body: self.rw_block(body)?,
+            },
+
             Stmt::If { test, success, failure } => Stmt::If {
                 test: self.rw_cond(test)?,
                 success: self.rw_block(success)?,
                 failure: self.rw_block(failure)?,
             },
 
+            Stmt::Join { group } => Stmt::Join {
+                group: self.rw_expr(group)?,
+            },
+
+            Stmt::Leave { group } => Stmt::Leave {
+                group: self.rw_expr(group)?,
+            },
+
             Stmt::Let { value, name } => Stmt::Let {
                 value: self.rw_expr(value)?,
                 name: self.rw_id_assign(name)?,
@@ -110,21 +134,38 @@ pub trait Rewriter {
                 or_else: self.rw_block(or_else)?,
             },
 
-            Stmt::Naked { message, target } => Stmt::Naked {
+            Stmt::Naked { message, target, speaker, tags } => Stmt::Naked {
                 message: message, // FIXME: Add hook to rewrite this
                 target: self.rw_expr(target)?,
+                speaker: speaker,
+                tags: tags,
             },
 
             Stmt::Recur { target } => Stmt::Recur {
                 target: self.rw_call(target)?,
             },
 
+            Stmt::Register { name } => Stmt::Register {
+                name: self.rw_expr(name)?,
+            },
+
             Stmt::Return { result } => Stmt::Return {
                 result: result,
             },
 
-            Stmt::Say { message } => Stmt::Say {
+            Stmt::Say { message, speaker, tags } => Stmt::Say {
                 message: self.rw_expr(message)?,
+                speaker: speaker,
+                tags: tags,
+            },
+
+            Stmt::SetGlobal { name, value } => Stmt::SetGlobal {
+                name: name,
+                value: self.rw_expr(value)?,
+            },
+
+            Stmt::Section { name } => Stmt::Section {
+                name: self.rw_label(name)?,
             },
 
             Stmt::SendMsg { target, message } => Stmt::SendMsg {
@@ -132,10 +173,18 @@ pub trait Rewriter {
                 message: self.rw_expr(message)?,
             },
 
+            Stmt::SourceLine { line } => Stmt::SourceLine {
+                line: line,
+            },
+
             Stmt::Trace { value } => Stmt::Trace {
                 value: self.rw_expr(value)?,
             },
 
+            Stmt::Visit { key } => Stmt::Visit {
+                key: self.rw_visit_key(key)?,
+            },
+
             Stmt::Trap { name, arms } => Stmt::Trap {
                 name: self.rw_label(name)?,
                 arms: each(arms, |t| {
@@ -151,6 +200,12 @@ pub trait Rewriter {
                 })?,
             },
 
+            Stmt::Tunnel { target } => Stmt::Tunnel {
+                target: self.rw_call(target)?,
+            },
+
+            Stmt::Unwind => Stmt::Unwind,
+
             Stmt::Wait { value } => Stmt::Wait {
                 value: self.rw_expr(value)?,
             },
@@ -163,6 +218,16 @@ pub trait Rewriter {
                         guard: self.rw_cond(t.guard)?,
                         message: self.rw_expr(t.message)?,
                         body: self.rw_block(t.body)?,
+                        once: t.once,
+                        tags: t.tags,
+                        locked: match t.locked {
+                            Some(reason) => Some(self.rw_expr(reason)?),
+                            None => None,
+                        },
+                        timeout: match t.timeout {
+                            Some(seconds) => Some(self.rw_expr(seconds)?),
+                            None => None,
+                        },
                     };
                     Ok(t)
                 })?,
@@ -178,6 +243,13 @@ pub trait Rewriter {
             Pat::List(items) => Pat::List({
                 each(items, |t| self.rw_pat(t))?
             }),
+            Pat::Cons(items, rest) => Pat::Cons({
+                each(items, |t| self.rw_pat(t))?
+            }, Box::new(self.rw_pat(*rest)?)),
+            Pat::Map(entries) => Pat::Map({
+                each(entries, |(key, pat)| Ok((key, self.rw_pat(pat)?)))?
+            }),
+            Pat::Typed(pat, ty) => Pat::Typed(Box::new(self.rw_pat(*pat)?), ty),
         })
     }
 
@@ -185,7 +257,7 @@ pub trait Rewriter {
         Ok(match t {
             Expr::Atom(a) => Expr::Atom(a),
             Expr::Int(n) => Expr::Int(n),
-            Expr::Str(s) => Expr::Str(s),
+            Expr::Str(s) => self.rw_str(s)?,
 
             Expr::PidOfSelf => Expr::PidOfSelf,
             Expr::PidZero => Expr::PidZero,
@@ -196,6 +268,12 @@ pub trait Rewriter {
                 Box::new(self.rw_cond(*cond)?)
             }),
 
+            Expr::Global(name) => Expr::Global(name),
+
+            Expr::Group(name) => Expr::Group({
+                Box::new(self.rw_expr(*name)?)
+            }),
+
             Expr::Id(v) => {
                 self.rw_id_eval(v)?
             },
@@ -208,6 +286,38 @@ pub trait Rewriter {
                 Box::new(self.rw_expr(*list)?)
             }, n),
 
+            Expr::Index(list, index) => Expr::Index({
+                Box::new(self.rw_expr(*list)?)
+            }, {
+                Box::new(self.rw_expr(*index)?)
+            }),
+
+            Expr::Drop(list, n) => Expr::Drop({
+                Box::new(self.rw_expr(*list)?)
+            }, n),
+
+            Expr::Builtin(builtin, args) => Expr::Builtin(builtin, {
+                each(args, |t| self.rw_expr(t))?
+            }),
+
+            Expr::Map(entries) => Expr::Map({
+                each(entries, |(key, value)| Ok((key, self.rw_expr(value)?)))?
+            }),
+
+            Expr::Update(map, entries) => Expr::Update({
+                Box::new(self.rw_expr(*map)?)
+            }, {
+                each(entries, |(key, value)| Ok((key, self.rw_expr(value)?)))?
+            }),
+
+            Expr::Field(map, key) => Expr::Field({
+                Box::new(self.rw_expr(*map)?)
+            }, key),
+
+            Expr::TryField(map, key) => Expr::TryField({
+                Box::new(self.rw_expr(*map)?)
+            }, key),
+
             Expr::Op(op, args) => Expr::Op(op, {
                 each(args, |t| self.rw_expr(t))?
             }),
@@ -220,9 +330,33 @@ pub trait Rewriter {
                 each(items, |t| self.rw_expr(t))?
             }),
 
+            Expr::Format(template, args) => Expr::Format({
+                Box::new(self.rw_expr(*template)?)
+            }, {
+                each(args, |t| self.rw_expr(t))?
+            }),
+
             Expr::Spawn(call) => Expr::Spawn({
                 self.rw_call(call)?
             }),
+
+            Expr::Vary(site) => Expr::Vary(site),
+
+            Expr::Visits(key) => Expr::Visits({
+                self.rw_visit_key(key)?
+            }),
+        })
+    }
+
+    fn rw_visit_key(&mut self, t: VisitKey) -> Try<VisitKey> {
+        Ok(match t {
+            VisitKey::Scene(name) => VisitKey::Scene({
+                self.rw_scene_name(name)?
+            }),
+
+            VisitKey::Choice(label, i) => VisitKey::Choice({
+                self.rw_label(label)?
+            }, i),
         })
     }
 
@@ -244,6 +378,23 @@ pub trait Rewriter {
                 Cond::HasLength(list, length)
             },
 
+            Cond::MinLength(list, length) => {
+                let list = self.rw_expr(list)?;
+                Cond::MinLength(list, length)
+            },
+
+            Cond::IsTrue(value) => Cond::IsTrue(self.rw_expr(value)?),
+
+            Cond::HasKey(map, key) => {
+                let map = self.rw_expr(map)?;
+                Cond::HasKey(map, key)
+            },
+
+            Cond::HasType(value, ty) => {
+                let value = self.rw_expr(value)?;
+                Cond::HasType(value, ty)
+            },
+
             Cond::True => Cond::True,
             Cond::False => Cond::False,
             Cond::LastResort => Cond::LastResort,
@@ -271,6 +422,10 @@ pub trait Rewriter {
         Ok(Expr::Id(t))
     }
 
+    fn rw_str(&mut self, t: Str) -> Try<Expr> {
+        Ok(Expr::Str(t))
+    }
+
     fn rw_id_assign(&mut self, t: Ident) -> Try<Ident> {
         Ok(t)
     }
@@ -14,6 +14,13 @@ pub enum ErrReason {
     InvalidCamelCase,
     InvalidSnakeCase,
     InvalidScreamingCase,
+    NoSuchBuiltin,
+    NoSuchType,
+}
+
+/// Finds the (1-based) line number of a byte offset into the source text.
+pub fn line_number(text: &str, offset: usize) -> u32 {
+    1 + text[.. offset].matches('\n').count() as u32
 }
 
 fn error<T>(r: ErrReason, l: usize) -> Result<T, TokErr> {
@@ -25,16 +32,29 @@ pub enum Tok<'input> {
     EndLn,
     EndBlk,
 
+    KwAs,
     KwDisarm,
+    KwElse,
+    KwExport,
+    KwFalse,
     KwFrom,
     KwGiven,
+    KwGroup,
     KwIf,
+    KwImport,
+    KwJoin,
+    KwLeave,
     KwLet,
     KwListen,
+    KwMatch,
+    KwRegister,
+    KwSeen,
     KwSpawn,
     KwThen,
     KwTrace,
     KwTrap,
+    KwTrue,
+    KwVisits,
     KwWait,
     KwWeave,
     KwWhen,
@@ -43,6 +63,7 @@ pub enum Tok<'input> {
     NmLabel(&'input str),
     NmMacro(&'input str),
     NmVar(&'input str),
+    NmGlobal(&'input str),
 
     LitAtom(&'input str),
     LitInt(&'input str),
@@ -179,6 +200,8 @@ impl<'input> Tokenizer<'input> {
 
                 '?' => { self.bump(); Some(self.screaming_case(i0)) },
 
+                '$' => { self.bump(); Some(self.global_name(i0)) },
+
                 '\'' => { self.bump(); Some(self.snake_case(i0)) },
 
                 '#' => { self.bump(); Some(self.snake_case(i0)) },
@@ -246,16 +269,29 @@ impl<'input> Tokenizer<'input> {
         }
 
         let token = match &self.text[start .. end] {
+            "as" => Tok::KwAs,
             "disarm" => Tok::KwDisarm,
+            "else" => Tok::KwElse,
+            "export" => Tok::KwExport,
+            "false" => Tok::KwFalse,
             "from" => Tok::KwFrom,
             "given" => Tok::KwGiven,
+            "group" => Tok::KwGroup,
             "if" => Tok::KwIf,
+            "import" => Tok::KwImport,
+            "join" => Tok::KwJoin,
+            "leave" => Tok::KwLeave,
             "let" => Tok::KwLet,
             "listen" => Tok::KwListen,
+            "match" => Tok::KwMatch,
+            "register" => Tok::KwRegister,
+            "seen" => Tok::KwSeen,
             "spawn" => Tok::KwSpawn,
             "then" => Tok::KwThen,
             "trace" => Tok::KwTrace,
             "trap" => Tok::KwTrap,
+            "true" => Tok::KwTrue,
+            "visits" => Tok::KwVisits,
             "wait" => Tok::KwWait,
             "weave" => Tok::KwWeave,
             "when" => Tok::KwWhen,
@@ -285,6 +321,17 @@ impl<'input> Tokenizer<'input> {
         Ok((start, Tok::NmVar(contents), end))
     }
 
+    fn global_name(&mut self, start: usize) -> TokResult<Tok<'input>> {
+        match self.lookahead {
+            Some((_, c)) if c.is_uppercase() => (),
+            _ => return error(ErrReason::InvalidCamelCase, start + 1),
+        }
+
+        let (start, _, end) = self.camel_case(start)?;
+        let contents = &self.text[start .. end];
+        Ok((start, Tok::NmGlobal(contents), end))
+    }
+
     fn screaming_case(&mut self, start: usize) -> TokResult<Tok<'input>> {
         let mut end = start;
         while let Some((i, c)) = self.lookahead {
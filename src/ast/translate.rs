@@ -30,6 +30,9 @@ impl DesugaredProgram {
 
             scenes: HashMap::new(),
             labels: HashMap::new(),
+            sections: HashMap::new(),
+
+            origin: None,
         };
 
         let label_zero = builder.create_block()?;
@@ -83,6 +86,12 @@ struct Builder {
 
     scenes: HashMap<ast::QfdSceneName, ir::Label>,
     labels: HashMap<ast::QfdLabel, ir::Label>,
+
+    /// Blocks which start at each section, created when first mentioned.
+    sections: HashMap<ast::QfdLabel, ir::Label>,
+
+    /// Source position of the statement being translated.
+    origin: Option<ir::SourcePos>,
 }
 
 impl Block {
@@ -117,6 +126,13 @@ impl Block {
         }
     }
 
+    fn len(&self) -> usize {
+        match self {
+            &Block::Partial(_, ref ops) => ops.len(),
+            &Block::Complete(ref block) => block.ops.len(),
+        }
+    }
+
     fn info(&mut self) -> &mut ir::BlockInfo {
         match self {
             &mut Block::Partial(ref mut info, _) => info,
@@ -132,12 +148,37 @@ impl Builder {
         let info = ir::BlockInfo {
             id: id,
             flags_needed: 0,
+            lines: self.origin.iter().map(|pos| (0, pos.clone())).collect(),
         };
 
         self.blocks.push(Block::Partial(info, vec![]));
         Ok(ir::Label(id))
     }
 
+    fn locate(&mut self, module: &ast::Modpath, scene: Option<String>) {
+        self.origin = Some(ir::SourcePos {
+            module: format!("{}", module),
+            scene: scene,
+            line: 0,
+        });
+    }
+
+    fn mark(&mut self, line: u32) -> Try<()> {
+        let pos = match self.origin.as_mut() {
+            Some(pos) => {
+                pos.line = line;
+                pos.clone()
+            },
+
+            None => ice!("Source line outside of any module"),
+        };
+
+        let block = self.current()?;
+        let index = block.len();
+        block.info().lines.push((index, pos));
+        Ok(())
+    }
+
     fn emit(&mut self, op: ir::Op) -> Try<()> {
         self.current()?.push(op)
     }
@@ -233,13 +274,38 @@ impl Builder {
         self.assign_temp(ir::Rvalue::Const(a))
     }
 
+    /// Lays out map entries as a list of alternating keys and values.
+    fn tr_entries(&mut self, t: Vec<(ast::Atom, ast::Expr)>) -> Try<ir::Var> {
+        let items = t.into_iter().flat_map(|(key, value)| {
+            vec![ast::Expr::Atom(key), value]
+        }).collect();
+
+        self.tr_expr(ast::Expr::List(items))
+    }
+
+    fn intern_visit_key(&mut self, t: ast::VisitKey) -> Try<ir::AtomId> {
+        let key = match t {
+            ast::VisitKey::Scene(name) => {
+                format!("{}", name.qualified()?)
+            },
+
+            ast::VisitKey::Choice(label, i) => {
+                format!("{}/{}", label.qualified()?, i)
+            },
+        };
+
+        Ok(self.atom_table.get_or_intern(key))
+    }
+
     fn tr_program(mut self, t: DesugaredProgram) -> Try<ir::Program> {
         self.jump(ir::Label(0))?;
         for (modpath, body) in t.preludes {
+            self.locate(&modpath, None);
             self.tr_block(body)?;
             self.capture_env(modpath)?;
         }
         self.current()?.exit(ir::Exit::EndProcess)?;
+        self.origin = None;
 
         for scene in t.scenes {
             self.tr_scene(scene)?;
@@ -278,11 +344,19 @@ impl Builder {
             env: env.id,
         };
 
-        let label = self.tr_scene_name(t.name)?;
+        let label = self.tr_scene_name(t.name.clone())?;
         self.ep_table.push((label, ep));
 
+        self.locate(&qfd.in_module, Some(format!("{}", qfd)));
         self.jump(label)?;
 
+        if let Some(&ast::Stmt::SourceLine { line }) = t.body.0.first() {
+            self.mark(line)?;
+        }
+
+        let key = self.intern_visit_key(ast::VisitKey::Scene(t.name))?;
+        self.emit(ir::Op::Visit(key))?;
+
         for (name, value) in env.mappings.into_iter() {
             self.assign(&name, value)?;
         }
@@ -302,6 +376,8 @@ impl Builder {
     }
 
     fn tr_lambda(&mut self, t: ast::TrapLambda) -> Try<()> {
+        let ast::QfdLabel { in_scene, .. } = t.label.qualified()?;
+
         let ep = ir::EntryPoint::Lambda {
             name: format!("{}", t.label.qualified()?),
         };
@@ -309,8 +385,13 @@ impl Builder {
         let label = self.tr_label(t.label)?;
         self.ep_table.push((label, ep));
 
+        self.locate(&in_scene.in_module, Some(format!("{}", in_scene)));
         self.jump(label)?;
 
+        if let Some(&ast::Stmt::SourceLine { line }) = t.body.0.first() {
+            self.mark(line)?;
+        }
+
         // NOTE: Environment is built dynamically by Stmt::Arm
         for (i, ast::Ident { name }) in t.captures.into_iter().enumerate() {
             self.assign(&name, ir::Rvalue::LoadEnv(i as u32))?;
@@ -343,13 +424,59 @@ impl Builder {
                 Ok(())
             },
 
-            ast::Stmt::If { test, success, failure } => {
-                let test = self.tr_cond(test)?;
+            ast::Stmt::Expand { name, .. } => {
+                ice!("Macro ?{} was not expanded", name)
+            },
+
+            ast::Stmt::Expanded { body, .. } => self.tr_block(body),
 
+            ast::Stmt::Divert { target } => {
+                let target = self.tr_section(target)?;
+                self.current()?.exit(ir::Exit::Goto(target))?;
+
+                let unreachable = self.create_block()?;
+                self.jump(unreachable)
+            },
+
+            ast::Stmt::SetGlobal { name, value } => {
+                let value = self.tr_expr(value)?;
+                let name = self.atom_table.get_or_intern(name);
+                self.emit(ir::Op::SetGlobal(name, value))
+            },
+
+            ast::Stmt::Section { name } => {
+                let label = self.tr_section(name)?;
+                self.current()?.exit(ir::Exit::Goto(label))?;
+                self.jump(label)
+            },
+
+            ast::Stmt::If { test, success, failure } => {
                 let succ = self.create_block()?;
                 let fail = self.create_block()?;
                 let next = self.create_block()?;
 
+                // Each part of an `and` is only tested once the ones before
+                // it have passed, so a guard never sees a message of the
+                // wrong shape
+                let mut tests = match test {
+                    ast::Cond::And(tests) => tests,
+                    other => vec![other],
+                };
+
+                let last = tests.pop().unwrap_or(ast::Cond::True);
+
+                for test in tests.into_iter() {
+                    let test = self.tr_cond(test)?;
+                    let more = self.create_block()?;
+
+                    self.current()?
+                        .exit(ir::Exit::IfThenElse(test, more, fail))?;
+
+                    self.jump(more)?;
+                }
+
+                let test = self.tr_cond(last)?;
+
                 self.current()?
                     .exit(ir::Exit::IfThenElse(test, succ, fail))?;
 
@@ -368,6 +495,16 @@ impl Builder {
                 self.jump(next)
             },
 
+            ast::Stmt::Join { group } => {
+                let group = self.tr_expr(group)?;
+                self.emit(ir::Op::Join(group))
+            },
+
+            ast::Stmt::Leave { group } => {
+                let group = self.tr_expr(group)?;
+                self.emit(ir::Op::Leave(group))
+            },
+
             ast::Stmt::Let { name: ast::Ident { name }, value } => {
                 let value = self.tr_expr(value)?;
                 self.assign(&name, ir::Rvalue::Var(value))?;
@@ -397,6 +534,25 @@ impl Builder {
                 self.jump(unreachable)
             },
 
+            ast::Stmt::Tunnel { target } => {
+                let ast::Call(scene, args) = target;
+                let scene = self.tr_scene_name(scene)?;
+                let argv = self.tr_expr(ast::Expr::List(args))?;
+                self.emit(ir::Op::Tunnel(scene.with_argv(argv)))
+            },
+
+            ast::Stmt::Unwind => {
+                self.current()?.exit(ir::Exit::Unwind)?;
+
+                let unreachable = self.create_block()?;
+                self.jump(unreachable)
+            },
+
+            ast::Stmt::Register { name } => {
+                let name = self.tr_expr(name)?;
+                self.emit(ir::Op::Register(name))
+            },
+
             ast::Stmt::Return { result } => {
                 self.current()?.exit(ir::Exit::Return(result))?;
 
@@ -404,9 +560,16 @@ impl Builder {
                 self.jump(unreachable)
             },
 
-            ast::Stmt::Say { message } => {
+            ast::Stmt::Say { message, speaker, tags } => {
                 let message = self.tr_expr(message)?;
-                self.emit(ir::Op::Say(message))
+
+                // Passed to the host as [[Speaker], [Tags...]]
+                let notes = self.tr_expr(ast::Expr::List(vec![
+                    ast::Expr::List(speaker.into_iter().map(ast::Expr::Atom).collect()),
+                    ast::Expr::List(tags.into_iter().map(ast::Expr::Atom).collect()),
+                ]))?;
+
+                self.emit(ir::Op::Say(message, notes))
             },
 
             ast::Stmt::SendMsg { message, target } => {
@@ -415,11 +578,20 @@ impl Builder {
                 self.emit(ir::Op::SendMsg(target, message))
             },
 
+            ast::Stmt::SourceLine { line } => {
+                self.mark(line)
+            },
+
             ast::Stmt::Trace { value } => {
                 let value = self.tr_expr(value)?;
                 self.emit(ir::Op::Trace(value))
             },
 
+            ast::Stmt::Visit { key } => {
+                let key = self.intern_visit_key(key)?;
+                self.emit(ir::Op::Visit(key))
+            },
+
             ast::Stmt::Wait { value } => {
                 let value = self.tr_expr(value)?;
                 self.emit(ir::Op::Wait(value))
@@ -441,6 +613,10 @@ impl Builder {
 
             ast::Expr::Str(s) => match s {
                 ast::Str::Plain(s) => self.intern_str(&s),
+
+                ast::Str::Varied(_) => {
+                    ice!("Variations must be desugared before translation")
+                },
             },
 
             ast::Expr::Int(i) => {
@@ -452,6 +628,11 @@ impl Builder {
                 self.assign_temp(ir::Rvalue::FromBool(flag))
             },
 
+            ast::Expr::Group(name) => {
+                let name = self.tr_expr(*name)?;
+                self.assign_temp(ir::Rvalue::Group(name))
+            },
+
             ast::Expr::Id(id) => self.eval(&id.name),
 
             ast::Expr::List(items) => {
@@ -497,6 +678,62 @@ impl Builder {
                 self.assign_temp(ir::Rvalue::Load(list.at_offset(index)))
             },
 
+            ast::Expr::Index(list, index) => {
+                let list = self.tr_expr(*list)?;
+                let index = self.tr_expr(*index)?;
+                self.assign_temp(ir::Rvalue::Index(list, index))
+            },
+
+            ast::Expr::Drop(list, n) => {
+                let list = self.tr_expr(*list)?;
+                self.assign_temp(ir::Rvalue::Drop(list, n))
+            },
+
+            ast::Expr::Builtin(builtin, args) => {
+                if args.len() != builtin.arity() {
+                    ice!("Wrong number of arguments to {}", builtin);
+                }
+
+                let mut vars = Vec::with_capacity(args.len());
+                for arg in args.into_iter() {
+                    vars.push(self.tr_expr(arg)?);
+                }
+
+                self.assign_temp(match builtin {
+                    ast::Builtin::Length => ir::Rvalue::Length(vars[0]),
+                    ast::Builtin::Append => ir::Rvalue::Append(vars[0], vars[1]),
+                    ast::Builtin::Concat => ir::Rvalue::Concat(vars[0], vars[1]),
+                    ast::Builtin::ToString => ir::Rvalue::ToString(vars[0]),
+                    ast::Builtin::Roll => ir::Rvalue::Roll(vars[0], vars[1]),
+                    ast::Builtin::Member => ir::Rvalue::Member(vars[0], vars[1]),
+                })
+            },
+
+            ast::Expr::Map(entries) => {
+                let entries = self.tr_entries(entries)?;
+                self.assign_temp(ir::Rvalue::Map(entries))
+            },
+
+            ast::Expr::Update(map, entries) => {
+                let map = self.tr_expr(*map)?;
+                let entries = self.tr_entries(entries)?;
+                self.assign_temp(ir::Rvalue::Update(map, entries))
+            },
+
+            ast::Expr::Field(map, key) => {
+                let map = self.tr_expr(*map)?;
+                let ast::Atom::User(key) = key;
+                let key = self.atom_table.get_or_intern(key);
+                self.assign_temp(ir::Rvalue::Field(map, key))
+            },
+
+            ast::Expr::TryField(map, key) => {
+                let map = self.tr_expr(*map)?;
+                let ast::Atom::User(key) = key;
+                let key = self.atom_table.get_or_intern(key);
+                self.assign_temp(ir::Rvalue::TryField(map, key))
+            },
+
             ast::Expr::Spawn(call) => {
                 let ast::Call(scene, args) = call;
                 let scene = self.tr_scene_name(scene)?;
@@ -504,6 +741,43 @@ impl Builder {
                 self.assign_temp(ir::Rvalue::Spawn(scene.with_argv(argv)))
             },
 
+            ast::Expr::Global(name) => {
+                let name = self.atom_table.get_or_intern(name);
+                self.assign_temp(ir::Rvalue::Global(name))
+            },
+
+            ast::Expr::Visits(key) => {
+                let key = self.intern_visit_key(key)?;
+                self.assign_temp(ir::Rvalue::Visits(key))
+            },
+
+            ast::Expr::Vary(site) => {
+                let key = self.atom_table.get_or_intern(site.name);
+                let len = site.items.len() as u32;
+
+                // The empty string is said once the items run out
+                let items = site.items.into_iter()
+                    .chain(Some(String::new()))
+                    .map(|item| ast::Expr::Str(ast::Str::Plain(item)))
+                    .collect();
+
+                let items = self.tr_expr(ast::Expr::List(items))?;
+
+                let kind = match site.kind {
+                    ast::VariationKind::Sequence => ir::Variation::Sequence,
+                    ast::VariationKind::Cycle => ir::Variation::Cycle,
+                    ast::VariationKind::Once => ir::Variation::Once,
+                    ast::VariationKind::Shuffle => ir::Variation::Shuffle,
+                };
+
+                self.assign_temp(ir::Rvalue::Vary(ir::VarySite {
+                    key: key,
+                    kind: kind,
+                    len: len,
+                    per_actor: site.per_actor,
+                }, items))
+            },
+
             ast::Expr::PidOfSelf => {
                 self.assign_temp(ir::Rvalue::PidOfSelf)
             },
@@ -516,6 +790,15 @@ impl Builder {
                 self.assign_temp(ir::Rvalue::Splice(items))
             },
 
+            ast::Expr::Format(template, args) => {
+                let template = self.tr_expr(*template)?;
+                let args = args.into_iter().map(|arg| {
+                    self.tr_expr(arg)
+                }).collect::<Try<_>>()?;
+
+                self.assign_temp(ir::Rvalue::Format(template, args))
+            },
+
             ast::Expr::Arg(n) => {
                 self.assign_temp(ir::Rvalue::LoadArg(n))
             },
@@ -537,6 +820,36 @@ impl Builder {
                 self.set(ir::Tvalue::HasLen(list, len))
             },
 
+            ast::Cond::MinLength(list, len) => {
+                let list = self.tr_expr(list)?;
+                self.set(ir::Tvalue::MinLen(list, len))
+            },
+
+            ast::Cond::IsTrue(value) => {
+                let value = self.tr_expr(value)?;
+                self.set(ir::Tvalue::IsTrue(value))
+            },
+
+            ast::Cond::HasType(value, ty) => {
+                let value = self.tr_expr(value)?;
+                self.set(ir::Tvalue::HasType(value, match ty {
+                    ast::Type::Int => ir::TypeTag::Int,
+                    ast::Type::Bool => ir::TypeTag::Bool,
+                    ast::Type::Atom => ir::TypeTag::Atom,
+                    ast::Type::Str => ir::TypeTag::Str,
+                    ast::Type::List => ir::TypeTag::List,
+                    ast::Type::Actor => ir::TypeTag::Actor,
+                    ast::Type::Map => ir::TypeTag::Map,
+                }))
+            },
+
+            ast::Cond::HasKey(map, key) => {
+                let map = self.tr_expr(map)?;
+                let ast::Atom::User(key) = key;
+                let key = self.atom_table.get_or_intern(key);
+                self.set(ir::Tvalue::HasKey(map, key))
+            },
+
             ast::Cond::Compare(rel, lhs, rhs) => {
                 let lhs = self.tr_expr(lhs)?;
                 let rhs = self.tr_expr(rhs)?;
@@ -560,6 +873,10 @@ impl Builder {
                     ast::BoolOp::Lte => {
                         self.set(ir::Tvalue::Lte(lhs, rhs))
                     },
+
+                    ast::BoolOp::Same => {
+                        self.set(ir::Tvalue::Same(lhs, rhs))
+                    },
                 }
             },
 
@@ -594,6 +911,18 @@ impl Builder {
         }
     }
 
+    fn tr_section(&mut self, t: ast::Label) -> Try<ir::Label> {
+        let t = t.qualified()?;
+
+        if let Some(&label) = self.sections.get(&t) {
+            return Ok(label);
+        }
+
+        let label = self.create_block()?;
+        self.sections.insert(t, label);
+        Ok(label)
+    }
+
     fn tr_scene_name(&mut self, t: ast::SceneName) -> Try<ir::Label> {
         let t = t.qualified()?;
         match self.scenes.get(&t) {
@@ -50,6 +50,13 @@ pub trait Visitor {
     fn visit_weave_arm(&mut self, t: &WeaveArm) -> Try<()> {
         self.visit_cond(&t.guard)?;
         self.visit_expr(&t.message)?;
+        each(&t.tags, |t| self.visit_atom(t))?;
+        if let Some(ref reason) = t.locked {
+            self.visit_expr(reason)?;
+        }
+        if let Some(ref seconds) = t.timeout {
+            self.visit_expr(seconds)?;
+        }
         self.visit_block(&t.body)
     }
 
@@ -86,12 +93,32 @@ pub trait Visitor {
                 self.visit_expr(value)?;
             },
 
+            &Stmt::Divert { ref target } => {
+                self.visit_divert(target)?;
+            },
+
+            &Stmt::Expand { ref args, .. } => {
+                each(args, |t| self.visit_expr(t))?;
+            },
+
+            &Stmt::Expanded { ref body, .. } => {
+                self.visit_block(body)?;
+            },
+
             &Stmt::If { ref test, ref success, ref failure } => {
                 self.visit_cond(test)?;
                 self.visit_block(success)?;
                 self.visit_block(failure)?;
             },
 
+            &Stmt::Join { ref group } => {
+                self.visit_expr(group)?;
+            },
+
+            &Stmt::Leave { ref group } => {
+                self.visit_expr(group)?;
+            },
+
             &Stmt::Let { ref value, ref name } => {
                 self.visit_expr(value)?;
                 self.visit_id_assign(name)?;
@@ -113,19 +140,42 @@ pub trait Visitor {
                 self.visit_block(or_else)?;
             },
 
-            &Stmt::Naked { ref message, ref target } => {
+            &Stmt::Naked { ref message, ref target, ref speaker, ref tags } => {
                 self.visit_string(message)?;
                 self.visit_expr(target)?;
+                if let &Some(ref speaker) = speaker {
+                    self.visit_atom(speaker)?;
+                }
+
+                each(tags, |t| self.visit_atom(t))?;
             },
 
             &Stmt::Recur { ref target } => {
                 self.visit_call(target)?;
             },
 
+            &Stmt::Register { ref name } => {
+                self.visit_expr(name)?;
+            },
+
             &Stmt::Return { ref result } => { let _ = result; () },
 
-            &Stmt::Say { ref message } => {
+            &Stmt::Say { ref message, ref speaker, ref tags } => {
                 self.visit_expr(message)?;
+                if let &Some(ref speaker) = speaker {
+                    self.visit_atom(speaker)?;
+                }
+
+                each(tags, |t| self.visit_atom(t))?;
+            },
+
+            &Stmt::SetGlobal { ref name, ref value } => {
+                self.visit_expr(value)?;
+                self.visit_global(name)?;
+            },
+
+            &Stmt::Section { ref name } => {
+                self.visit_label(name)?;
             },
 
             &Stmt::SendMsg { ref target, ref message } => {
@@ -133,15 +183,27 @@ pub trait Visitor {
                 self.visit_expr(target)?;
             },
 
+            &Stmt::SourceLine { .. } => (),
+
             &Stmt::Trace { ref value } => {
                 self.visit_expr(value)?;
             },
 
+            &Stmt::Visit { ref key } => {
+                self.visit_visit_key(key)?;
+            },
+
             &Stmt::Trap { ref name, ref arms } => {
                 self.visit_label(name)?;
                 each(arms, |t| self.visit_trap_arm(t))?;
             },
 
+            &Stmt::Tunnel { ref target } => {
+                self.visit_call(target)?;
+            },
+
+            &Stmt::Unwind => (),
+
             &Stmt::Wait { ref value } => {
                 self.visit_expr(value)?;
             },
@@ -163,6 +225,14 @@ pub trait Visitor {
                 self.visit_atom(atom)
             },
 
+            &Expr::Global(ref name) => {
+                self.visit_global(name)
+            },
+
+            &Expr::Group(ref name) => {
+                self.visit_expr(name.as_ref())
+            },
+
             &Expr::Id(ref ident) => {
                 self.visit_id_eval(ident)
             },
@@ -193,6 +263,44 @@ pub trait Visitor {
                 self.visit_expr(expr.as_ref())
             },
 
+            &Expr::Index(ref list, ref index) => {
+                self.visit_expr(list.as_ref())?;
+                self.visit_expr(index.as_ref())
+            },
+
+            &Expr::Drop(ref list, _) => {
+                self.visit_expr(list.as_ref())
+            },
+
+            &Expr::Builtin(ref builtin, ref args) => {
+                self.visit_builtin(builtin, args)
+            },
+
+            &Expr::Map(ref entries) => {
+                each(entries, |&(ref key, ref value)| {
+                    self.visit_atom(key)?;
+                    self.visit_expr(value)
+                })
+            },
+
+            &Expr::Update(ref map, ref entries) => {
+                self.visit_expr(map.as_ref())?;
+                each(entries, |&(ref key, ref value)| {
+                    self.visit_atom(key)?;
+                    self.visit_expr(value)
+                })
+            },
+
+            &Expr::Field(ref map, ref key) => {
+                self.visit_expr(map.as_ref())?;
+                self.visit_atom(key)
+            },
+
+            &Expr::TryField(ref map, ref key) => {
+                self.visit_expr(map.as_ref())?;
+                self.visit_atom(key)
+            },
+
             &Expr::Spawn(ref target) => {
                 self.visit_call(target)
             },
@@ -201,6 +309,19 @@ pub trait Visitor {
                 each(elems, |t| self.visit_expr(t))
             },
 
+            &Expr::Format(ref template, ref args) => {
+                self.visit_expr(template.as_ref())?;
+                each(args, |t| self.visit_expr(t))
+            },
+
+            &Expr::Vary(ref site) => {
+                self.visit_vary(site)
+            },
+
+            &Expr::Visits(ref key) => {
+                self.visit_visit_key(key)
+            },
+
             &Expr::PidOfSelf => Ok(()),
 
             &Expr::PidZero => Ok(()),
@@ -219,6 +340,23 @@ pub trait Visitor {
                 self.visit_expr(expr)?;
             },
 
+            &Cond::MinLength(ref expr, _) => {
+                self.visit_expr(expr)?;
+            },
+
+            &Cond::IsTrue(ref expr) => {
+                self.visit_expr(expr)?;
+            },
+
+            &Cond::HasKey(ref expr, ref key) => {
+                self.visit_expr(expr)?;
+                self.visit_atom(key)?;
+            },
+
+            &Cond::HasType(ref expr, _) => {
+                self.visit_expr(expr)?;
+            },
+
             &Cond::Compare(ref op, ref lhs, ref rhs) => {
                 let _ = op;
                 self.visit_expr(lhs)?;
@@ -254,9 +392,39 @@ pub trait Visitor {
             &Pat::List(ref list) => {
                 each(list, |t| self.visit_pattern(t))
             },
+
+            &Pat::Cons(ref list, ref rest) => {
+                each(list, |t| self.visit_pattern(t))?;
+                self.visit_pattern(rest)
+            },
+
+            &Pat::Map(ref entries) => {
+                each(entries, |&(ref key, ref pat)| {
+                    self.visit_atom(key)?;
+                    self.visit_pattern(pat)
+                })
+            },
+
+            &Pat::Typed(ref pat, _) => self.visit_pattern(pat),
+        }
+    }
+
+    fn visit_visit_key(&mut self, t: &VisitKey) -> Try<()> {
+        match t {
+            &VisitKey::Scene(ref name) => {
+                self.visit_scene_name(name)
+            },
+
+            &VisitKey::Choice(ref label, _) => {
+                self.visit_label(label)
+            },
         }
     }
 
+    fn visit_builtin(&mut self, _t: &Builtin, args: &[Expr]) -> Try<()> {
+        each(args, |t| self.visit_expr(t))
+    }
+
     fn visit_atom(&mut self, _t: &Atom) -> Try<()> {
         Ok(())
     }
@@ -269,17 +437,30 @@ pub trait Visitor {
         Ok(())
     }
 
+    fn visit_global(&mut self, _t: &str) -> Try<()> {
+        Ok(())
+    }
+
     fn visit_label(&mut self, _t: &Label) -> Try<()> {
         Ok(())
     }
 
+    fn visit_divert(&mut self, t: &Label) -> Try<()> {
+        self.visit_label(t)
+    }
+
     fn visit_scene_name(&mut self, _t: &SceneName) -> Try<()> {
         Ok(())
     }
 
+    fn visit_vary(&mut self, _t: &VarySite) -> Try<()> {
+        Ok(())
+    }
+
     fn visit_string(&mut self, t: &Str) -> Try<()> {
         match t {
-            &Str::Plain(_) => Ok(())
+            &Str::Plain(_) => Ok(()),
+            &Str::Varied(_) => Ok(()),
         }
     }
 }
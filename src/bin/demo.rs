@@ -2,7 +2,8 @@ extern crate souvenir;
 extern crate clap;
 
 use souvenir::ast::Program;
-use souvenir::driver::Try;
+use souvenir::driver::{Try, LoadErr};
+use souvenir::locale;
 
 fn main() {
     use clap::{App, Arg};
@@ -16,22 +17,72 @@ fn main() {
              .index(2)
              .required(true)
              .help("Scene to perform"))
+        .arg(Arg::with_name("break")
+             .long("break")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .help("Pause when an actor enters this scene"))
+        .arg(Arg::with_name("translation")
+             .long("translation")
+             .takes_value(true)
+             .help("Show text from a .po or .csv string table"))
         .get_matches();
 
     let filename = matches.value_of("PATH").unwrap();
     let scene = matches.value_of("SCENE").unwrap();
 
-    run_demo(&filename, &scene)
+    let breakpoints = match matches.values_of("break") {
+        Some(values) => values.collect(),
+        None => vec![],
+    };
+
+    let translation = matches.value_of("translation");
+
+    run_demo(&filename, &scene, &breakpoints, translation)
         .unwrap();
 }
 
 use std::path::Path;
 
-fn run_demo<P: AsRef<Path>>(path: P, scene: &str) -> Try<()> {
-    let program = Program::load_from_path(path.as_ref())?.compile()?;
+use souvenir::vm::{ActorId, Scheduler};
+
+fn run_demo<P: AsRef<Path>>(path: P, scene: &str, breakpoints: &[&str], translation: Option<&str>) -> Try<()> {
+    let build = Program::load_from_path(path.as_ref())?.build()?;
+
+    for warning in build.warnings {
+        println!("Warning: {}", warning);
+    }
+
+    let mut program = build.program;
+
+    if let Some(filename) = translation {
+        use std::fs::File;
+        use std::io::Read;
+
+        let mut source = String::new();
+        File::open(filename).and_then(|mut f| f.read_to_string(&mut source))
+            .map_err(LoadErr::from)?;
+
+        let table = if filename.ends_with(".csv") {
+            locale::read_csv(&source)?
+        } else {
+            locale::read_po(&source)?
+        };
+
+        for id in program.translate(&table) {
+            println!("Warning: Placeholders don't match in {}", id);
+        }
+    }
 
     let mut interpreter = program.init().unwrap();
 
+    for name in breakpoints {
+        if let Err(err) = interpreter.break_at_scene(name) {
+            println!("Can't break at {}: {:?}", name, err);
+        }
+    }
+
     let actor = interpreter.spawn(scene, vec![]).unwrap();
 
     loop {
@@ -39,6 +90,7 @@ fn run_demo<P: AsRef<Path>>(path: P, scene: &str) -> Try<()> {
 
         if let Some(signal) = interpreter.read() {
             use souvenir::vm::OutSignal;
+            use std::time::Instant;
 
             match signal {
                 OutSignal::Exit(id) => {
@@ -48,29 +100,65 @@ fn run_demo<P: AsRef<Path>>(path: P, scene: &str) -> Try<()> {
                     }
                 },
 
-                OutSignal::Hcf(_, err) => {
+                OutSignal::Hcf(_, err, pos) => {
                     println!("Process died with an error: {:?}", err);
+                    if let Some(pos) = pos {
+                        println!("  at {}", pos);
+                    }
                     break;
                 },
 
-                OutSignal::Trace(_, value) => {
+                OutSignal::Trace(_, value, _) => {
                     println!("{}", value);
                 },
 
                 OutSignal::Say(token) => {
-                    println!("{}", token.content());
+                    match token.speaker() {
+                        Some(name) => println!("{}: {}", name, token.content()),
+                        None => println!("{}", token.content()),
+                    }
+
                     interpreter.write(token.reply().into());
                 },
 
                 OutSignal::Ask(token) => {
-                    let choices = token.content().iter().map(|&(i, ref value)| {
-                        (i, String::from(value.clone()))
+                    let choices = token.content().iter().map(|choice| {
+                        let text = String::from(choice.text.clone());
+                        let text = match choice.reason {
+                            Some(ref why) if !choice.available => {
+                                let why = String::from(why.clone());
+                                format!("({}) [{}]", text, why)
+                            },
+
+                            _ if !choice.available => format!("({})", text),
+
+                            _ => text,
+                        };
+
+                        (choice.index, text)
                     }).collect::<Vec<_>>();
 
+                    if let Some(deadline) = token.deadline() {
+                        let left = deadline - interpreter.now();
+                        println!("(Quick! You have {} seconds.)", left);
+                    }
+
+                    let started = Instant::now();
                     let pick = ask_user(choices);
+                    let elapsed = started.elapsed();
+                    interpreter.advance({
+                        elapsed.as_secs() as f32
+                            + elapsed.subsec_nanos() as f32 * 1e-9
+                    });
+
                     interpreter.write(token.reply(pick).into());
                 },
 
+                OutSignal::Break(id, addr) => {
+                    println!("Breakpoint: {:?} at {}", id, addr);
+                    debug_actor(&mut interpreter, id);
+                },
+
                 //_ => (),
             }
         }
@@ -79,6 +167,52 @@ fn run_demo<P: AsRef<Path>>(path: P, scene: &str) -> Try<()> {
     Ok(())
 }
 
+fn debug_actor(interpreter: &mut Scheduler, id: ActorId) {
+    use std::io::stdin;
+
+    let mut input = String::with_capacity(128);
+
+    loop {
+        println!("(s)tep, (c)ontinue, (i)nspect, (r)egisters, (h)eap?");
+
+        input.clear();
+        if stdin().read_line(&mut input).unwrap() == 0 {
+            return interpreter.resume(id).unwrap_or(());
+        }
+
+        let result = match input.trim() {
+            "s" => interpreter.step(id).map(|()| {
+                if let Some(info) = interpreter.inspect(id) {
+                    println!("{:?} at {}", info.state, info.pc);
+                }
+            }),
+
+            "c" => return interpreter.resume(id).unwrap_or(()),
+
+            "i" => Ok(println!("{:#?}", interpreter.inspect(id))),
+
+            "r" => interpreter.registers(id).map(|registers| {
+                for (reg, value) in registers {
+                    println!("{}\t{}", reg, value);
+                }
+            }),
+
+            "h" => interpreter.heap(id).map(|lists| {
+                for (addr, value) in lists {
+                    println!(".{:X}\t{}", addr.0, value);
+                }
+            }),
+
+            _ => continue,
+        };
+
+        if let Err(err) = result {
+            println!("Debugger error: {:?}", err);
+            return;
+        }
+    }
+}
+
 fn ask_user(choices: Vec<(i32, String)>) -> i32 {
     use std::io::stdin;
 
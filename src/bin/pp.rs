@@ -44,7 +44,13 @@ fn pp(path: &str, cmd: Cmd) -> Try<()> {
         },
 
         Cmd::DumpRem => {
-            println!("{}", program.compile()?);
+            let build = program.build()?;
+
+            for warning in build.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+
+            println!("{}", build.program);
         },
     };
 
@@ -0,0 +1,46 @@
+extern crate souvenir;
+extern crate clap;
+
+use souvenir::ast::Program;
+use souvenir::driver::Try;
+use souvenir::locale;
+
+fn main() {
+    use clap::{App, Arg};
+
+    let matches = App::new("Souvenir string table exporter")
+        .arg(Arg::with_name("PATH")
+             .index(1)
+             .required(true)
+             .help("Path to export strings from"))
+        .arg(Arg::with_name("format")
+             .long("format")
+             .takes_value(true)
+             .possible_values(&["po", "csv"])
+             .default_value("po")
+             .help("Format to write to standard output"))
+        .get_matches();
+
+    let path = matches.value_of("PATH").unwrap();
+    let format = matches.value_of("format").unwrap();
+
+    export(&path, &format).unwrap();
+}
+
+use std::path::Path;
+
+fn export<P: AsRef<Path>>(path: P, format: &str) -> Try<()> {
+    use std::io::stdout;
+
+    let entries = Program::load_from_path(path.as_ref())?.strings()?;
+
+    let stdout = stdout();
+    let mut out = stdout.lock();
+
+    match format {
+        "csv" => locale::write_csv(&entries, &mut out),
+        _ => locale::write_po(&entries, &mut out),
+    }.expect("Couldn't write to standard output");
+
+    Ok(())
+}
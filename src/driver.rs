@@ -3,8 +3,10 @@ use std::io;
 use std::path::Path;
 
 use ast::{self, Program, Modpath, Module, ParseErr};
+use ast::pass::DesugaredProgram;
 
 use vm;
+use locale;
 
 macro_rules! ice {
     ( $( $arg:tt )* ) => {
@@ -45,7 +47,29 @@ pub enum LoadErr {
 pub enum BuildErr {
     NoSuchModule(Modpath),
     NoSuchScene(ast::QfdSceneName),
+    SceneIsPrivate(ast::QfdSceneName),
+    AliasRedefined(String),
     NoSuchLabel(ast::Label),
+    NoSuchMacro(String),
+    MacroRedefined(String),
+    RecursiveMacro {
+        name: String,
+        defined_at: u32,
+    },
+    WrongNumberOfMacroArgs {
+        name: String,
+        defined_at: u32,
+        used_at: u32,
+        wanted: usize,
+        got: usize,
+    },
+    VarOnlyInMacro {
+        var: String,
+        name: String,
+        defined_at: u32,
+        expanded_at: u32,
+        used_at: u32,
+    },
     NoSuchVar(String),
     InvalidNumber(String),
     InvalidAssignToSelf(ast::Stmt),
@@ -61,12 +85,42 @@ pub enum BuildErr {
         wanted: usize,
         got: usize,
     },
+    WrongNumberOfBuiltinArgs {
+        builtin: ast::Builtin,
+        wanted: usize,
+        got: usize,
+    },
+    TypeMismatch {
+        expected: ast::Type,
+        found: ast::Type,
+    },
+    ArgTypeMismatch {
+        scene: ast::QfdSceneName,
+        arg: usize,
+        expected: ast::Type,
+        found: ast::Type,
+    },
+    ImpureGuard(ast::Expr),
+    UnreachableArm {
+        arm: usize,
+        covered_by: usize,
+    },
+    NonExhaustiveMatch {
+        missing: ast::Pat,
+    },
     MultipleErrors(Vec<BuildErrWithCtx>),
 }
 
 #[derive(Clone, Debug)]
 pub struct BuildErrWithCtx(pub BuildErr, pub ErrCtx);
 
+/// A compiled program, and what the compiler noticed along the way.
+#[derive(Debug)]
+pub struct Compiled {
+    pub program: vm::Program,
+    pub warnings: Vec<BuildErrWithCtx>,
+}
+
 impl Program {
     pub fn load_from_path(path: &Path) -> Result<Self, LoadErr> {
         let mut dirs = Vec::with_capacity(16);
@@ -135,12 +189,41 @@ impl Program {
         })
     }
 
+    /// Lists the program's strings for translation. See `locale`.
+    pub fn strings(self) -> Result<Vec<locale::Entry>, CompileErr> {
+        let (desugared, _) = self.front_end()?;
+        desugared.strings()
+    }
+
+    /// Checks the program and desugars it. Everything which needs the final
+    /// shape of the program starts from here, so string IDs found for
+    /// translation are the same ones the compiled program uses.
+    fn front_end(self) -> Result<(DesugaredProgram, Vec<BuildErrWithCtx>), CompileErr> {
+        let program = self.with_stdlib()?.expand_macros()?.resolve_imports()?;
+        let warnings = program.check_patterns()?;
+        program.check_names()?;
+        program.check_prelude_restrictions()?;
+        program.check_sections()?;
+        program.check_guards()?;
+        let desugared = program.desugar()?;
+        desugared.check_types()?;
+        Ok((desugared, warnings))
+    }
+
     pub fn compile(self) -> Result<vm::Program, CompileErr> {
-        self.check_names()?;
-        self.check_prelude_restrictions()?;
-        let ir = self.desugar()?.translate()?;
+        Ok(self.build()?.program)
+    }
+
+    /// Compiles the program, along with warnings about likely mistakes which
+    /// don't stop it from compiling.
+    pub fn build(self) -> Result<Compiled, CompileErr> {
+        let (desugared, warnings) = self.front_end()?;
+        let ir = desugared.translate()?;
         let bytecode = ir.optimize()?.translate()?;
-        Ok(bytecode)
+        Ok(Compiled {
+            program: bytecode,
+            warnings: warnings,
+        })
     }
 }
 
@@ -47,6 +47,13 @@ pub type StrId = ::vm::StrId;
 
 //#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub type AtomId = ::vm::AtomId;
+pub type TypeTag = ::vm::TypeTag;
+
+pub type VarySite = ::vm::VarySite;
+
+pub type Variation = ::vm::Variation;
+
+pub type SourcePos = ::vm::SourcePos;
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum ConstRef {
@@ -65,6 +72,9 @@ pub struct Block {
 pub struct BlockInfo {
     pub id: u32,
     pub flags_needed: u32,
+
+    /// Source positions of the ops in this block, keyed by op index.
+    pub lines: Vec<(usize, SourcePos)>,
 }
 
 #[derive(Clone, Debug)]
@@ -91,13 +101,19 @@ pub enum Op {
     Disarm(Label),
     //Discard(Rvalue),
     Export(Env, Var),
+    Join(Var),
+    Leave(Var),
     Let(Var, Rvalue),
     Listen(TrapRef),
-    Say(Var),
+    Register(Var),
+    Say(Var, Var),
     Store(Var, Ptr),
     SendMsg(Var, Var),
     Set(Flag, Tvalue),
+    SetGlobal(AtomId, Var),
     Trace(Var),
+    Tunnel(FnCall),
+    Visit(AtomId),
     Wait(Var),
 }
 
@@ -108,6 +124,7 @@ pub enum Exit {
     IfThenElse(Flag, Label, Label),
     Recur(FnCall),
     Return(bool),
+    Unwind,
 }
 
 #[derive(Clone, Debug)]
@@ -123,11 +140,27 @@ pub enum Rvalue {
     LoadArg(u32),
     LoadEnv(u32),
     FromBool(Flag),
+    Global(AtomId),
+    Group(Var),
     Spawn(FnCall),
     Splice(Vec<Var>),
+    Format(Var, Vec<Var>),
     Alloc(u32),
+    Length(Var),
+    Append(Var, Var),
+    Concat(Var, Var),
+    ToString(Var),
+    Member(Var, Var),
+    Index(Var, Var),
+    Drop(Var, u32),
+    Map(Var),
+    Update(Var, Var),
+    Field(Var, AtomId),
+    TryField(Var, AtomId),
     Const(ConstRef),
     MenuChoice(Var),
+    Visits(AtomId),
+    Vary(VarySite, Var),
     PidOfSelf,
 }
 
@@ -135,12 +168,16 @@ pub enum Rvalue {
 pub enum Tvalue {
     Flag(Flag),
     Eql(Var, Var),
+    Same(Var, Var),
     Gt(Var, Var),
     Lt(Var, Var),
     Gte(Var, Var),
     Lte(Var, Var),
     HasLen(Var, u32),
-    Nonzero(Var),
+    MinLen(Var, u32),
+    HasKey(Var, AtomId),
+    HasType(Var, TypeTag),
+    IsTrue(Var),
     True,
     False,
     And(Vec<Flag>),
@@ -11,12 +11,14 @@ use driver::Try;
 
 impl ir::Program {
     pub fn translate(self) -> Try<vm::Program> {
-        let (env_table, scene_table) = self.build_tables()?;
+        let (env_table, scene_table, lambda_table) = self.build_tables()?;
 
         let mut translator = Translator {
             registers: self.alloc_registers()?,
             env_table: env_table,
             scene_table: scene_table,
+            lambda_table: lambda_table,
+            source_map: vm::SourceMap::new(),
             code: Vec::new(),
             jump_table: vm::JumpTable::with_capacity(self.blocks.len()),
             str_table: self.str_table,
@@ -34,16 +36,26 @@ impl ir::Program {
             atom_table: translator.atom_table,
             env_table: translator.env_table,
             scene_table: translator.scene_table,
+            lambda_table: translator.lambda_table,
+            source_map: translator.source_map,
+            translations: HashMap::new(),
         })
     }
 
-    pub fn build_tables(&self) -> Try<(vm::EnvTable, vm::SceneTable)> {
+    pub fn build_tables(&self) -> Try<(vm::EnvTable, vm::SceneTable, vm::LambdaTable)> {
         let mut scene_table = vm::SceneTable::new();
         let mut env_table = vm::EnvTable::new();
+        let mut lambda_table = vm::LambdaTable::new();
 
         for (ir::Label(label), ep) in self.ep_table.iter().cloned() {
             let (name, argc, ir::Env(env_id)) = match ep {
                 ir::EntryPoint::Scene { name, argc, env } => (name, argc, env),
+
+                ir::EntryPoint::Lambda { name } => {
+                    lambda_table.insert(vm::Label(label), name);
+                    continue;
+                },
+
                 _ => continue,
             };
 
@@ -59,7 +71,7 @@ impl ir::Program {
             env_table.insert(label, env_id);
         }
 
-        Ok((env_table, scene_table))
+        Ok((env_table, scene_table, lambda_table))
     }
 }
 
@@ -67,6 +79,8 @@ struct Translator {
     registers: HashMap<ir::Var, vm::Reg>,
     env_table: vm::EnvTable,
     scene_table: vm::SceneTable,
+    lambda_table: vm::LambdaTable,
+    source_map: vm::SourceMap,
     code: Vec<vm::Instr>,
     jump_table: vm::JumpTable,
     str_table: StringInterner<vm::StrId>,
@@ -90,13 +104,42 @@ impl Translator {
             Err(err) => ice!("{:?}", err),
         };
 
-        for op in t.ops.into_iter() {
+        let mut lines = t.info.lines.into_iter().peekable();
+
+        for (i, op) in t.ops.into_iter().enumerate() {
+            while lines.peek().map_or(false, |&(at, _)| at <= i) {
+                let (_, pos) = lines.next().unwrap();
+                self.locate(pos)?;
+            }
+
             self.tr_op(op)?;
         }
 
+        for (_, pos) in lines {
+            self.locate(pos)?;
+        }
+
         self.tr_exit(t.exit)
     }
 
+    fn locate(&mut self, pos: vm::SourcePos) -> Try<()> {
+        let addr = match vm::InstrAddr::checked_from(self.code.len()) {
+            Some(addr) => addr,
+            None => ice!("Source map overflow"),
+        };
+
+        // Later positions at the same address supersede earlier ones
+        if let Some(&mut (last, ref mut prev)) = self.source_map.last_mut() {
+            if last == addr {
+                *prev = pos;
+                return Ok(());
+            }
+        }
+
+        self.source_map.push((addr, pos));
+        Ok(())
+    }
+
     fn tr_op(&mut self, t: ir::Op) -> Try<()> {
         type Binop = fn(vm::Reg, vm::Reg) -> vm::Instr;
 
@@ -177,7 +220,7 @@ impl Translator {
 
                 ir::Rvalue::Roll(lhs, rhs) => {
                     fn roll(a: vm::Reg, b: vm::Reg) -> vm::Instr {
-                        vm::Instr::Blocking(vm::Io::Roll(b, a))
+                        vm::Instr::Blocking(vm::Io::Roll(a, b))
                     }
 
                     tr_binop(self, roll, lhs, rhs, dst)
@@ -225,6 +268,17 @@ impl Translator {
                     self.emit(vm::Instr::Reify(src, dst))
                 },
 
+                ir::Rvalue::Global(name) => {
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Blocking(vm::Io::LoadGlobal(name, dst)))
+                },
+
+                ir::Rvalue::Group(src) => {
+                    let dst = self.tr_var(dst)?;
+                    let src = self.tr_var(src)?;
+                    self.emit(vm::Instr::Group(src, dst))
+                },
+
                 ir::Rvalue::Spawn(call) => {
                     let dst = self.tr_var(dst)?;
                     let argv = self.tr_var(call.argv)?;
@@ -235,8 +289,40 @@ impl Translator {
                     }))
                 },
 
-                ir::Rvalue::Splice(_vars) => {
-                    ice!("Unimplemented: splice")
+                ir::Rvalue::Splice(vars) => {
+                    let dst = self.tr_var(dst)?;
+                    let size = vm::ListLen(vars.len() as u32);
+                    self.emit(vm::Instr::Alloc(size, dst))?;
+
+                    for (i, var) in vars.into_iter().enumerate() {
+                        let src = self.tr_var(var)?;
+
+                        self.emit(vm::Instr::Write(src, vm::Ptr {
+                            addr: dst,
+                            offset: i as u32,
+                        }))?;
+                    }
+
+                    self.emit(vm::Instr::Splice(dst, dst))
+                },
+
+                ir::Rvalue::Format(template, args) => {
+                    let dst = self.tr_var(dst)?;
+                    let size = vm::ListLen(args.len() as u32 + 1);
+                    self.emit(vm::Instr::Alloc(size, dst))?;
+
+                    let vars = Some(template).into_iter().chain(args);
+
+                    for (i, var) in vars.enumerate() {
+                        let src = self.tr_var(var)?;
+
+                        self.emit(vm::Instr::Write(src, vm::Ptr {
+                            addr: dst,
+                            offset: i as u32,
+                        }))?;
+                    }
+
+                    self.emit(vm::Instr::Format(dst, dst))
                 },
 
                 ir::Rvalue::Alloc(size) => {
@@ -245,12 +331,100 @@ impl Translator {
                     self.emit(vm::Instr::Alloc(size, dst))
                 },
 
+                ir::Rvalue::Length(list) => {
+                    let list = self.tr_var(list)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Len(list, dst))
+                },
+
+                ir::Rvalue::Append(lhs, rhs) => {
+                    let lhs = self.tr_var(lhs)?;
+                    let rhs = self.tr_var(rhs)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Append(lhs, rhs, dst))
+                },
+
+                ir::Rvalue::Concat(lhs, rhs) => {
+                    let lhs = self.tr_var(lhs)?;
+                    let rhs = self.tr_var(rhs)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Concat(lhs, rhs, dst))
+                },
+
+                ir::Rvalue::ToString(value) => {
+                    let value = self.tr_var(value)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::ToStr(value, dst))
+                },
+
+                ir::Rvalue::Member(item, list) => {
+                    let item = self.tr_var(item)?;
+                    let list = self.tr_var(list)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Member(item, list, dst))
+                },
+
+                ir::Rvalue::Index(list, index) => {
+                    let list = self.tr_var(list)?;
+                    let index = self.tr_var(index)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Index(list, index, dst))
+                },
+
+                ir::Rvalue::Drop(list, n) => {
+                    let list = self.tr_var(list)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Drop(list, vm::ListLen(n), dst))
+                },
+
+                ir::Rvalue::Map(entries) => {
+                    let entries = self.tr_var(entries)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Map(entries, dst))
+                },
+
+                ir::Rvalue::Update(map, entries) => {
+                    let map = self.tr_var(map)?;
+                    let entries = self.tr_var(entries)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Update(map, entries, dst))
+                },
+
+                ir::Rvalue::Field(map, key) => {
+                    let map = self.tr_var(map)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Field(map, key, dst))
+                },
+
+                ir::Rvalue::TryField(map, key) => {
+                    let map = self.tr_var(map)?;
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::TryField(map, key, dst))
+                },
+
+                ir::Rvalue::Visits(key) => {
+                    let dst = self.tr_var(dst)?;
+                    self.emit(vm::Instr::Blocking(vm::Io::CountVisits(key, dst)))
+                },
+
+                ir::Rvalue::Vary(site, items) => {
+                    let items = self.tr_var(items)?;
+                    let dst = self.tr_var(dst)?;
+                    let io = vm::Io::Vary(site, items, dst);
+                    self.emit(vm::Instr::Blocking(io))
+                },
+
                 ir::Rvalue::PidOfSelf => {
                     let dst = self.tr_var(dst)?;
                     self.emit(vm::Instr::Blocking(vm::Io::GetPid(dst)))
                 },
             },
 
+            ir::Op::SetGlobal(name, src) => {
+                let src = self.tr_var(src)?;
+                self.emit(vm::Instr::Blocking(vm::Io::StoreGlobal(name, src)))
+            },
+
             ir::Op::Set(dst, value) => match value {
                 ir::Tvalue::Flag(src) => {
                     let src = self.tr_flag(src)?;
@@ -265,6 +439,25 @@ impl Translator {
                     self.emit(vm::Instr::CheckSize(len, list, dst))
                 },
 
+                ir::Tvalue::MinLen(list, len) => {
+                    let list = self.tr_var(list)?;
+                    let len = vm::ListLen(len);
+                    let dst = self.tr_flag(dst)?;
+                    self.emit(vm::Instr::CheckMinSize(len, list, dst))
+                },
+
+                ir::Tvalue::HasType(value, tag) => {
+                    let value = self.tr_var(value)?;
+                    let dst = self.tr_flag(dst)?;
+                    self.emit(vm::Instr::CheckType(tag, value, dst))
+                },
+
+                ir::Tvalue::HasKey(map, key) => {
+                    let map = self.tr_var(map)?;
+                    let dst = self.tr_flag(dst)?;
+                    self.emit(vm::Instr::CheckKey(key, map, dst))
+                },
+
                 ir::Tvalue::Eql(lhs, rhs) => {
                     let lhs = self.tr_var(lhs)?;
                     let rhs = self.tr_var(rhs)?;
@@ -272,6 +465,13 @@ impl Translator {
                     self.emit(vm::Instr::Eql(lhs, rhs, dst))
                 },
 
+                ir::Tvalue::Same(lhs, rhs) => {
+                    let lhs = self.tr_var(lhs)?;
+                    let rhs = self.tr_var(rhs)?;
+                    let dst = self.tr_flag(dst)?;
+                    self.emit(vm::Instr::Same(lhs, rhs, dst))
+                },
+
                 ir::Tvalue::Gt(lhs, rhs) => {
                     let lhs = self.tr_var(lhs)?;
                     let rhs = self.tr_var(rhs)?;
@@ -319,10 +519,10 @@ impl Translator {
                     self.emit(vm::Instr::Not(dst))
                 },
 
-                ir::Tvalue::Nonzero(src) => {
+                ir::Tvalue::IsTrue(src) => {
                     let src = self.tr_var(src)?;
                     let dst = self.tr_flag(dst)?;
-                    self.emit(vm::Instr::Nonzero(src, dst))
+                    self.emit(vm::Instr::IsTrue(src, dst))
                 },
 
                 ir::Tvalue::And(flags) => {
@@ -346,15 +546,31 @@ impl Translator {
                 },
             },
 
+            ir::Op::Join(var) => {
+                let var = self.tr_var(var)?;
+                self.emit(vm::Instr::Blocking(vm::Io::Join(var)))
+            },
+
+            ir::Op::Leave(var) => {
+                let var = self.tr_var(var)?;
+                self.emit(vm::Instr::Blocking(vm::Io::Leave(var)))
+            },
+
             ir::Op::Listen(trap_ref) => {
                 let env = self.tr_var(trap_ref.env)?;
                 let label = self.tr_label(trap_ref.label)?;
                 self.emit(vm::Instr::Blocking(vm::Io::ArmAtomic(env, label)))
             },
 
-            ir::Op::Say(var) => {
+            ir::Op::Register(var) => {
+                let var = self.tr_var(var)?;
+                self.emit(vm::Instr::Blocking(vm::Io::Register(var)))
+            },
+
+            ir::Op::Say(var, notes) => {
                 let var = self.tr_var(var)?;
-                self.emit(vm::Instr::Blocking(vm::Io::Say(var)))
+                let notes = self.tr_var(notes)?;
+                self.emit(vm::Instr::Blocking(vm::Io::Say(var, notes)))
             },
 
             ir::Op::SendMsg(target, message) => {
@@ -381,9 +597,19 @@ impl Translator {
                 self.emit(vm::Instr::Blocking(vm::Io::Trace(var)))
             },
 
-            ir::Op::Wait(_val) => {
-                // FIXME: Actually translate time units
-                self.emit(vm::Instr::Blocking(vm::Io::Sleep(9000.0)))
+            ir::Op::Tunnel(ir::FnCall { argv, label }) => {
+                let label = self.tr_label(label)?;
+                let argv = self.tr_var(argv)?;
+                self.emit(vm::Instr::Blocking(vm::Io::Tunnel(argv, label)))
+            },
+
+            ir::Op::Visit(key) => {
+                self.emit(vm::Instr::Blocking(vm::Io::Visit(key)))
+            },
+
+            ir::Op::Wait(var) => {
+                let var = self.tr_var(var)?;
+                self.emit(vm::Instr::Blocking(vm::Io::Sleep(var)))
             },
         }
     }
@@ -418,6 +644,10 @@ impl Translator {
             ir::Exit::Return(result) => {
                 self.emit(vm::Instr::Return(result))
             },
+
+            ir::Exit::Unwind => {
+                self.emit(vm::Instr::Unwind)
+            },
         }
     }
 
@@ -35,6 +35,14 @@ pub trait Visitor {
                 self.visit_var_read(var)?;
             },
 
+            &Op::Join(ref var) => {
+                self.visit_var_read(var)?;
+            },
+
+            &Op::Leave(ref var) => {
+                self.visit_var_read(var)?;
+            },
+
             &Op::Let(ref name, ref value) => {
                 self.visit_rval(value)?;
                 self.visit_var_write(name)?;
@@ -45,8 +53,13 @@ pub trait Visitor {
                 self.visit_var_read(&trap_ref.env)?;
             },
 
-            &Op::Say(ref var) => {
+            &Op::Register(ref var) => {
+                self.visit_var_read(var)?;
+            },
+
+            &Op::Say(ref var, ref notes) => {
                 self.visit_var_read(var)?;
+                self.visit_var_read(notes)?;
             },
 
             &Op::Store(ref var, ref ptr) => {
@@ -64,10 +77,21 @@ pub trait Visitor {
                 self.visit_flag(flag)?;
             },
 
+            &Op::SetGlobal(_, ref var) => {
+                self.visit_var_read(var)?;
+            },
+
             &Op::Trace(ref var) => {
                 self.visit_var_read(var)?;
             },
 
+            &Op::Tunnel(ref call) => {
+                self.visit_label(&call.label)?;
+                self.visit_var_read(&call.argv)?;
+            },
+
+            &Op::Visit(_) => (),
+
             &Op::Wait(ref var) => {
                 self.visit_var_read(var)?;
             },
@@ -94,6 +118,8 @@ pub trait Visitor {
             },
 
             &Exit::Return(_) => (),
+
+            &Exit::Unwind => (),
         }
 
         Ok(())
@@ -144,6 +170,12 @@ pub trait Visitor {
                 self.visit_flag(flag)?;
             },
 
+            &Rvalue::Global(_) => (),
+
+            &Rvalue::Group(ref var) => {
+                self.visit_var_read(var)?;
+            },
+
             &Rvalue::Spawn(ref call) => {
                 self.visit_label(&call.label)?;
                 self.visit_var_read(&call.argv)?;
@@ -155,14 +187,76 @@ pub trait Visitor {
                 }
             },
 
+            &Rvalue::Format(ref template, ref args) => {
+                self.visit_var_read(template)?;
+                for var in args.iter() {
+                    self.visit_var_read(var)?;
+                }
+            },
+
             &Rvalue::Alloc(_) => (),
 
             &Rvalue::Const(_) => (),
 
+            &Rvalue::Length(ref list) => {
+                self.visit_var_read(list)?;
+            },
+
+            &Rvalue::Append(ref lhs, ref rhs) => {
+                self.visit_var_read(lhs)?;
+                self.visit_var_read(rhs)?;
+            },
+
+            &Rvalue::Concat(ref lhs, ref rhs) => {
+                self.visit_var_read(lhs)?;
+                self.visit_var_read(rhs)?;
+            },
+
+            &Rvalue::ToString(ref value) => {
+                self.visit_var_read(value)?;
+            },
+
+            &Rvalue::Member(ref item, ref list) => {
+                self.visit_var_read(item)?;
+                self.visit_var_read(list)?;
+            },
+
+            &Rvalue::Index(ref list, ref index) => {
+                self.visit_var_read(list)?;
+                self.visit_var_read(index)?;
+            },
+
+            &Rvalue::Drop(ref list, _) => {
+                self.visit_var_read(list)?;
+            },
+
+            &Rvalue::Map(ref entries) => {
+                self.visit_var_read(entries)?;
+            },
+
+            &Rvalue::Update(ref map, ref entries) => {
+                self.visit_var_read(map)?;
+                self.visit_var_read(entries)?;
+            },
+
+            &Rvalue::Field(ref map, _) => {
+                self.visit_var_read(map)?;
+            },
+
+            &Rvalue::TryField(ref map, _) => {
+                self.visit_var_read(map)?;
+            },
+
             &Rvalue::MenuChoice(ref var) => {
                 self.visit_var_read(var)?;
             },
 
+            &Rvalue::Visits(_) => (),
+
+            &Rvalue::Vary(_, ref items) => {
+                self.visit_var_read(items)?;
+            },
+
             &Rvalue::PidOfSelf => (),
         }
 
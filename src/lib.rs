@@ -7,6 +7,8 @@ pub mod driver;
 pub mod ast;
 pub mod ir;
 pub mod vm;
+pub mod locale;
+pub mod stdlib;
 
 pub mod vecmap;
 
@@ -0,0 +1,287 @@
+//! String tables for translators.
+//!
+//! Every string constant in a program has an ID, which stays the same as long
+//! as its text doesn't change. Lines with variations are exported as a single
+//! template, with a `{N}` placeholder where each variation goes; the items of
+//! each variation are exported separately. Translations must keep the same
+//! placeholders, or they'll be left out when loaded.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use driver::LoadErr;
+
+/// A string constant, as shown to a translator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Key to give the translation. See `vm::string_id()`.
+    pub id: String,
+
+    /// The text as written in the script.
+    pub text: String,
+
+    /// Scenes or modules which use the text.
+    pub contexts: Vec<String>,
+}
+
+pub type Translations = HashMap<String, String>;
+
+/// Writes a gettext template, with each ID used as the `msgctxt`.
+pub fn write_po<W: Write>(entries: &[Entry], out: &mut W) -> io::Result<()> {
+    writeln!(out, "msgid \"\"")?;
+    writeln!(out, "msgstr \"Content-Type: text/plain; charset=UTF-8\\n\"")?;
+
+    for entry in entries.iter() {
+        writeln!(out, "")?;
+        writeln!(out, "#: {}", entry.contexts.join(" "))?;
+        writeln!(out, "msgctxt \"{}\"", entry.id)?;
+        writeln!(out, "msgid \"{}\"", po_escape(&entry.text))?;
+        writeln!(out, "msgstr \"\"")?;
+    }
+
+    Ok(())
+}
+
+/// Writes a spreadsheet with the columns `id`, `context`, `source`, and an
+/// empty `translation`.
+pub fn write_csv<W: Write>(entries: &[Entry], out: &mut W) -> io::Result<()> {
+    writeln!(out, "id,context,source,translation")?;
+
+    for entry in entries.iter() {
+        writeln!(out, "{},{},{},", csv_quote(&entry.id), {
+            csv_quote(&entry.contexts.join(" "))
+        }, csv_quote(&entry.text))?;
+    }
+
+    Ok(())
+}
+
+/// Reads the translations from a gettext catalog written by `write_po()`.
+/// Entries with an empty `msgstr` are skipped.
+pub fn read_po(source: &str) -> Result<Translations, LoadErr> {
+    let mut table = Translations::new();
+
+    let mut context: Option<String> = None;
+    let mut translation: Option<String> = None;
+
+    // Which string a continuation line adds to
+    let mut field = None;
+
+    for (n, line) in source.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, rest) = match line.find(' ') {
+            _ if line.starts_with('"') => ("", line),
+            Some(i) => (&line[.. i], line[i ..].trim()),
+            None => return Err(po_err(n, "Expected a string")),
+        };
+
+        let value = po_unescape(rest).ok_or(po_err(n, "Bad string"))?;
+
+        match keyword {
+            "msgctxt" => {
+                finish_po(&mut table, context.take(), translation.take());
+                context = Some(value);
+                field = Some(PoField::Context);
+            },
+
+            "msgid" => field = Some(PoField::Id),
+
+            "msgstr" => {
+                translation = Some(value);
+                field = Some(PoField::Str);
+            },
+
+            "" => {
+                let continued = match field {
+                    Some(PoField::Context) => context.as_mut(),
+                    Some(PoField::Str) => translation.as_mut(),
+                    Some(PoField::Id) => continue,
+                    None => return Err(po_err(n, "Unexpected string")),
+                };
+
+                if let Some(text) = continued {
+                    text.push_str(&value);
+                }
+            },
+
+            other => {
+                let message = format!("Unknown keyword {:?}", other);
+                return Err(po_err(n, &message));
+            },
+        }
+    }
+
+    finish_po(&mut table, context, translation);
+
+    Ok(table)
+}
+
+/// Reads the `id` and `translation` columns from a spreadsheet. Rows with an
+/// empty translation are skipped.
+pub fn read_csv(source: &str) -> Result<Translations, LoadErr> {
+    let mut rows = csv_rows(source)?.into_iter();
+
+    let header = rows.next().unwrap_or_default();
+    let column = |name: &str| {
+        header.iter().position(|h| h.trim() == name).ok_or({
+            LoadErr::Parse(format!("CSV is missing a {:?} column", name))
+        })
+    };
+
+    let id = column("id")?;
+    let translation = column("translation")?;
+
+    let mut table = Translations::new();
+
+    for row in rows {
+        match (row.get(id), row.get(translation)) {
+            (Some(id), Some(text)) if !text.is_empty() => {
+                table.insert(id.clone(), text.clone());
+            },
+
+            _ => (),
+        }
+    }
+
+    Ok(table)
+}
+
+enum PoField {
+    Context,
+    Id,
+    Str,
+}
+
+fn finish_po(table: &mut Translations, ctx: Option<String>, text: Option<String>) {
+    if let (Some(ctx), Some(text)) = (ctx, text) {
+        if !text.is_empty() {
+            table.insert(ctx, text);
+        }
+    }
+}
+
+fn po_err(line: usize, message: &str) -> LoadErr {
+    LoadErr::Parse(format!("Line {}: {}", line + 1, message))
+}
+
+fn po_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+fn po_unescape(quoted: &str) -> Option<String> {
+    if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+        return None;
+    }
+
+    let mut out = String::with_capacity(quoted.len());
+    let mut chars = quoted[1 .. quoted.len() - 1].chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        out.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            other => other,
+        });
+    }
+
+    Some(out)
+}
+
+fn csv_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn csv_rows(source: &str) -> Result<Vec<Vec<String>>, LoadErr> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            },
+
+            '"' if field.is_empty() => quoted = true,
+
+            ',' if !quoted => row.push(field.split_off(0)),
+
+            '\r' if !quoted => (),
+
+            '\n' if !quoted => {
+                row.push(field.split_off(0));
+                rows.push(row.split_off(0));
+            },
+
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(LoadErr::Parse("Unterminated quote in CSV".to_owned()));
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+#[test]
+fn read_back_translations() {
+    let po = r#"msgid ""
+msgstr "Content-Type: text/plain; charset=UTF-8\n"
+
+#: start:start
+msgctxt "0123456789abcdef"
+msgid "{0} there!"
+msgstr "{0} "
+"toi !"
+
+msgctxt "fedcba9876543210"
+msgid "Untranslated"
+msgstr ""
+"#;
+
+    let table = read_po(po).unwrap();
+    assert_eq!(table.len(), 1);
+    assert_eq!(table["0123456789abcdef"], "{0} toi !");
+
+    let csv = "id,context,source,translation\n\
+               \"0123456789abcdef\",start:start,\"Hi\",\"Salut, \"\"toi\"\"\"\n";
+
+    let table = read_csv(csv).unwrap();
+    assert_eq!(table["0123456789abcdef"], "Salut, \"toi\"");
+}
@@ -0,0 +1,79 @@
+//! Modules bundled with the compiler, which every program can use under the
+//! `std:` path. A program which has its own module at one of these paths uses
+//! that instead, so a project can pin or patch a module it depends on.
+
+use ast::{Program, Modpath, Module};
+
+use driver::Try;
+
+/// Version of the bundled modules. Bumped whenever a scene is removed or
+/// changes what it replies with. Scripts can ask for it too, with
+/// `spawn std:version:check(Self)`, which replies `#version, 1, 0, 0`.
+pub const VERSION: &'static str = "1.0.0";
+
+const MODULES: &'static [(&'static str, &'static str)] = &[
+    ("dialogue", include_str!("dialogue.svr")),
+    ("list", include_str!("list.svr")),
+    ("random", include_str!("random.svr")),
+    ("timer", include_str!("timer.svr")),
+];
+
+/// Source of `std:version`, which tells scripts the `VERSION`.
+fn version_module() -> String {
+    format!("export check;\n\n== check(Dst)\n\nDst <- #version, {}\n", {
+        VERSION.replace(".", ", ")
+    })
+}
+
+fn modpath(name: &str) -> Modpath {
+    Modpath(vec!["std".to_owned(), name.to_owned()])
+}
+
+impl Program {
+    /// Adds each bundled module the program doesn't already provide.
+    pub fn with_stdlib(self) -> Try<Self> {
+        let mut modules = self.modules;
+
+        let version = version_module();
+        let sources = MODULES.iter()
+            .cloned()
+            .chain(Some(("version", &version[..])));
+
+        for (name, source) in sources {
+            let modpath = modpath(name);
+
+            if modules.iter().any(|&(ref path, _)| path == &modpath) {
+                continue;
+            }
+
+            let module = match Module::parse(source) {
+                Ok(module) => module,
+                Err(err) => ice!("Bundled module {} is broken: {:?}", modpath, err),
+            };
+
+            modules.push((modpath, module));
+        }
+
+        Ok(Program {
+            modules: modules,
+        })
+    }
+}
+
+#[test]
+fn programs_can_replace_bundled_modules() {
+    let timer = Module::parse("== after\n").unwrap();
+
+    let program = Program {
+        modules: vec![
+            (modpath("timer"), timer.clone()),
+        ],
+    };
+
+    let program = program.with_stdlib().unwrap();
+
+    assert_eq!(program.modules.len(), MODULES.len() + 1);
+    assert!(program.modules.contains(&(modpath("timer"), timer)));
+
+    program.compile().unwrap();
+}
@@ -1,9 +1,15 @@
 mod pretty_print;
 
-use std::collections::{HashMap, VecDeque};
+#[cfg(test)]
+mod tests;
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use string_interner::{StringInterner, NonNegative};
 
+use rand::{Rng, SeedableRng, XorShiftRng};
+
 use vecmap::*;
 
 /// Entry point to the interpreter API.
@@ -13,6 +19,12 @@ pub struct Scheduler {
     /// Processes which are alive and ready to run immediately.
     queue: RunQueue,
 
+    /// Named groups of processes which receive broadcast messages.
+    groups: ProcessGroups,
+
+    /// Actors stopped by the host, and the code positions that stop them.
+    debugger: Debugger,
+
     /// Buffer of processes presently being executed.
     workspace: VecDeque<Task>,
 
@@ -23,6 +35,36 @@ pub struct Scheduler {
 
     global_heap: Heap,
 
+    /// Names registered by live actors.
+    registry: HashMap<AtomId, ActorId>,
+
+    /// How many times each scene or weave choice has been visited.
+    visits: HashMap<AtomId, u32>,
+
+    /// Story variables shared by all actors. Values are kept in global_heap.
+    globals: HashMap<AtomId, Value>,
+
+    /// How many times each text variation has been used, across all actors.
+    variations: HashMap<AtomId, u32>,
+
+    /// Source of randomness for shuffled variations.
+    rng: XorShiftRng,
+
+    /// Seconds of story time, as advanced by the host.
+    clock: f32,
+
+    /// Timed questions, keyed by the actor waiting for an answer.
+    deadlines: HashMap<ActorId, Deadline>,
+
+    /// Actors waiting for the story clock, keyed by the actor.
+    alarms: HashMap<ActorId, Alarm>,
+
+    /// Orders lines from actors talking at once, if the host asked for it.
+    arbiter: Option<Arbiter>,
+
+    /// Messages sent during the current dispatch, awaiting delivery.
+    mailbag: VecDeque<Envelope>,
+
     next_pid: u32,
 
     next_event: u32,
@@ -35,14 +77,70 @@ struct RunQueue {
     dead: VecDeque<Box<Process>>,
 }
 
+/// Organizes processes by membership in broadcast groups.
+struct ProcessGroups {
+    members: HashMap<AtomId, Vec<ActorId>>,
+}
+
+/// Debugging state controlled by the host environment.
+struct Debugger {
+    paused: HashSet<ActorId>,
+    breakpoints: HashSet<InstrAddr>,
+}
+
 /// Program data marshalled for use by the host environment.
 #[derive(Clone, Debug)]
 pub enum RawValue {
     ActorId(ActorId),
     Atom(String),
+    Group(String),
     Int(i32),
+    Bool(bool),
     Str(String),
     List(Vec<RawValue>),
+    Map(Vec<(String, RawValue)>),
+
+    /// A slot which hasn't been written yet. Only the debugger sees these.
+    Undefined,
+}
+
+/// Hints written in the script about how to present a line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Annotations {
+    /// Who is speaking, as in `#mayor > Hello.`
+    pub speaker: Option<String>,
+
+    /// Trailing `#tags` on the line, in order.
+    pub tags: Vec<String>,
+}
+
+/// One of the options in a weave, as offered to the host.
+#[derive(Clone, Debug)]
+pub struct Choice {
+    /// Number to reply with if this option is picked.
+    pub index: i32,
+
+    pub text: RawValue,
+
+    /// Trailing `#tags` on the option, in order.
+    pub tags: Vec<String>,
+
+    /// False if the option is only shown for the player's information.
+    /// Replying with it asks the same question again.
+    pub available: bool,
+
+    /// Why the option can't be picked, as written in the script.
+    pub reason: Option<RawValue>,
+}
+
+/// Story state which outlives any one actor.
+#[derive(Clone, Debug, Default)]
+pub struct SaveData {
+    /// Story variables, as set with `let $Name = ...`.
+    pub globals: Vec<(String, RawValue)>,
+
+    /// Visit counts for scenes and weave choices.
+    pub visits: Vec<(String, u32)>,
 }
 
 /// Signals sent into the interpreter by the host environment. Cannot be cloned.
@@ -55,10 +153,67 @@ pub enum InSignal {
 /// Signals sent from the interpreter to the host environment. Cannot be cloned.
 pub enum OutSignal {
     Exit(ActorId),
-    Hcf(ActorId, RunErr),
+    Hcf(ActorId, RunErr, Option<SourcePos>),
     Say(SayToken),
     Ask(AskToken),
-    Trace(ActorId, RawValue),
+    Trace(ActorId, RawValue, Option<SourcePos>),
+    Break(ActorId, InstrAddr),
+}
+
+/// A stream of dialogue, such as the main conversation or background chatter.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    /// A line goes to the channel named by one of its tags, and a question
+    /// to the channel named by a tag on one of its choices. Anything else
+    /// goes to the first channel.
+    pub name: String,
+
+    /// Lines wait while any channel with a higher priority is busy.
+    pub priority: i32,
+}
+
+/// Snapshot of a dialogue channel, for the host's UI.
+#[derive(Clone, Debug)]
+pub struct ChannelInfo {
+    pub name: String,
+    pub priority: i32,
+
+    /// Actor whose line has been handed to the host and not yet answered.
+    pub speaking: Option<ActorId>,
+
+    /// Actors waiting for their turn, in the order they'll speak.
+    pub waiting: Vec<ActorId>,
+}
+
+/// Snapshot of a live actor, for debugging and introspection.
+#[derive(Clone, Debug)]
+pub struct ActorInfo {
+    pub id: ActorId,
+    pub state: ActorState,
+
+    /// Scene the actor entered most recently.
+    pub scene: Option<String>,
+
+    /// Trap handler the actor is currently running, if any.
+    pub lambda: Option<String>,
+
+    /// Traps the actor has armed, in the order they were armed.
+    pub traps: Vec<String>,
+
+    pub inbox_len: usize,
+    pub heap_size: usize,
+
+    /// Address of the next instruction the actor will execute.
+    pub pc: InstrAddr,
+}
+
+/// What an actor is doing, as seen by the host environment.
+#[derive(Copy, Clone, Debug)]
+pub enum ActorState {
+    Running,
+    Sleeping(Io),
+    Listening,
+    Dead,
 }
 
 /// Opaque key into the supervisor's list of processes.
@@ -74,11 +229,46 @@ struct Task {
 #[derive(Debug, Eq, Hash, PartialEq)]
 struct Tag(ActorId, u32);
 
+/// Holds back lines until their channel is free to deliver them.
+struct Arbiter {
+    channels: Vec<ChannelQueue>,
+}
+
+struct ChannelQueue {
+    config: Channel,
+    speaking: Option<Tag>,
+    waiting: VecDeque<OutSignal>,
+}
+
+/// A question which answers itself if the host takes too long.
+struct Deadline {
+    /// None while the arbiter holds the question back; the clock only
+    /// starts once the host can see it.
+    at: Option<f32>,
+    seconds: f32,
+    ticket: Tag,
+    dst: Reg,
+    index: i32,
+}
+
+/// A `wait` which ends once the story clock catches up with it.
+struct Alarm {
+    at: f32,
+    ticket: Tag,
+}
+
+/// A message in transit, along with the heap it was copied into.
+struct Envelope {
+    to: ActorId,
+    heap: Heap,
+    argv: Value,
+}
+
 // NB. No Copy, no Clone!
-pub struct SayToken(Tag, RawValue);
+pub struct SayToken(Tag, RawValue, Annotations);
 pub struct SayReplyToken(Tag);
-pub struct AskToken(Tag, Vec<(i32, RawValue)>, Reg);
-pub struct AskReplyToken(Tag, i32, Reg);
+pub struct AskToken(Tag, Vec<Choice>, Reg, Option<f32>);
+pub struct AskReplyToken(Tag, i32, Reg, Vec<Choice>);
 
 /// Executable program
 #[derive(Clone, Debug)]
@@ -100,6 +290,26 @@ pub struct Program {
 
     /// Map of (qualified) scene names to their corresponding entry points.
     pub scene_table: SceneTable,
+
+    /// Map of trap entry points to their (qualified) names.
+    pub lambda_table: LambdaTable,
+
+    /// Debug table mapping code back to script source. May be stripped.
+    pub source_map: SourceMap,
+
+    /// Replacements for string constants, as loaded with `translate()`.
+    pub translations: HashMap<StrId, String>,
+}
+
+/// Position in a script, for error reporting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourcePos {
+    pub module: String,
+
+    /// Scene the code belongs to, or `None` for a module prelude.
+    pub scene: Option<String>,
+
+    pub line: u32,
 }
 
 /// Unencoded (immediately executable) VM instructions.
@@ -115,6 +325,7 @@ pub enum Instr {
     Div(Reg, Reg),
     Mul(Reg, Reg),
     Eql(Reg, Reg, Flag),
+    Same(Reg, Reg, Flag),
     Gte(Reg, Reg, Flag),
     Lte(Reg, Reg, Flag),
     Gt(Reg, Reg, Flag),
@@ -126,17 +337,35 @@ pub enum Instr {
     True(Flag),
     False(Flag),
     Reify(Flag, Reg),
-    Nonzero(Reg, Flag),
+    Group(Reg, Reg),
+    IsTrue(Reg, Flag),
     CheckSize(ListLen, Reg, Flag),
+    CheckMinSize(ListLen, Reg, Flag),
+    CheckKey(AtomId, Reg, Flag),
+    CheckType(TypeTag, Reg, Flag),
     LoadLit(Value, Reg),
     Alloc(ListLen, Reg),
     Read(Ptr, Reg),
     Write(Reg, Ptr),
+    Splice(Reg, Reg),
+    Format(Reg, Reg),
+    Len(Reg, Reg),
+    Concat(Reg, Reg, Reg),
+    ToStr(Reg, Reg),
+    Member(Reg, Reg, Reg),
+    Append(Reg, Reg, Reg),
+    Index(Reg, Reg, Reg),
+    Drop(Reg, ListLen, Reg),
+    Map(Reg, Reg),
+    Update(Reg, Reg, Reg),
+    Field(Reg, AtomId, Reg),
+    TryField(Reg, AtomId, Reg),
     Jump(Label),
     JumpIf(Flag, Label),
     Arm(Reg, Label),
     Disarm(Label),
     Return(bool),
+    Unwind,
     Blocking(Io),
     Nop,
     Bye,
@@ -148,26 +377,56 @@ pub enum Instr {
 pub enum Io {
     Export(Reg, EnvId),
     Recur(Reg, Label),
+    Tunnel(Reg, Label),
     Spawn(Reg, Label, Reg),
     GetPid(Reg),
     SendMsg(Reg, Reg),
     Roll(Reg, Reg),
-    Sleep(f32),
+    Sleep(Reg),
     ArmAtomic(Reg, Label),
+    Register(Reg),
+    Join(Reg),
+    Leave(Reg),
     Trace(Reg),
+    Visit(AtomId),
+    CountVisits(AtomId, Reg),
+    LoadGlobal(AtomId, Reg),
+    StoreGlobal(AtomId, Reg),
+    Vary(VarySite, Reg, Reg),
     Native(Reg, NativeFn, Reg),
-    Say(Reg),
+    Say(Reg, Reg),
     Ask(Reg, Reg),
 }
 
+/// How a text variation picks its next alternative.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Variation {
+    Sequence,
+    Cycle,
+    Once,
+    Shuffle,
+}
+
+/// A set of alternatives in the source text, and how to choose between them.
+#[derive(Copy, Clone, Debug)]
+pub struct VarySite {
+    pub key: AtomId,
+    pub kind: Variation,
+    pub len: u32,
+    pub per_actor: bool,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Value {
     Int(i32),
+    Bool(bool),
     Atom(AtomId),
     ActorId(ActorId),
+    Group(AtomId),
     StrConst(StrId),
     StrAddr(u32),
     ListAddr(HeapAddr),
+    MapAddr(HeapAddr),
     Capacity(u32),
     Undefined,
 }
@@ -175,10 +434,13 @@ pub enum Value {
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TypeTag {
     Int,
+    Bool,
     Atom,
     Actor,
+    Group,
     Str,
     List,
+    Map,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -210,6 +472,11 @@ pub type EnvTable = HashMap<Label, EnvId>;
 
 pub type SceneTable = HashMap<String, SceneDef>;
 
+pub type LambdaTable = HashMap<Label, String>;
+
+/// Source positions, sorted by the address where each one takes effect.
+pub type SourceMap = Vec<(InstrAddr, SourcePos)>;
+
 pub struct StackFrame {
     gpr: [Value; REG_COUNT],
     flag: [bool; REG_COUNT],
@@ -232,6 +499,9 @@ pub struct Continuation {
 
     frame: StackFrame,
 
+    /// Handler currently running in this invocation.
+    label: Label,
+
     /// Sequence of remaining handlers in this invocation.
     queue: Vec<Trap>,
 }
@@ -241,10 +511,24 @@ pub struct Stack {
     upper: Option<Continuation>,
 }
 
-#[derive(Clone, Debug, Default)]
+/// Where to go back to when a tunnel unwinds.
+struct Tunnel {
+    return_addr: InstrAddr,
+    stack: Stack,
+    traps: Vec<Trap>,
+    scene: Label,
+
+    /// Messages for the caller, which the tunnel's own traps didn't take.
+    inbox: VecDeque<HeapAddr>,
+}
+
+#[derive(Clone, Debug)]
 pub struct Heap {
     values: Vec<Value>,
-    strings: Vec<String>,
+    strings: StringInterner<usize>,
+
+    /// Blocks laid out as maps rather than lists.
+    maps: HashSet<HeapAddr>,
 }
 
 pub struct Process {
@@ -254,6 +538,20 @@ pub struct Process {
     inbox: VecDeque<HeapAddr>,
     op: Instr,
     pc: InstrAddr,
+    scene: Label,
+
+    /// Variations used by this actor which keep their own count.
+    variations: HashMap<AtomId, u32>,
+
+    /// Callers of the tunnels this actor is in, innermost last.
+    tunnels: Vec<Tunnel>,
+
+    /// Set when stopped at a breakpoint, so it isn't hit again on resume.
+    resuming: bool,
+
+    /// When a wait which a trap cut short should end, if the handler goes
+    /// back to it.
+    wake_at: Option<f32>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -261,6 +559,7 @@ enum RunState {
     Blocked(Io),
     Running,
     Exiting,
+    Break,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -276,13 +575,23 @@ pub enum RunErr {
     UnallocatedAccess(usize),
     HeapCorrupted(Value),
     ListOutOfBounds(usize, u32),
+    NoSuchKey(AtomId),
     TypeMismatch(Value, TypeTag),
     DividedByZero,
+    InvalidRoll { count: i32, sides: i32, },
+    RollOverflow { count: i32, sides: i32, },
     Unrepresentable(usize),
     Uninitialized,
     UnrecognizedAtom,
     UnrecognizedSceneName,
     NoSuchAtom(AtomId),
+    NoSuchName(AtomId),
+    UnsetGlobal(AtomId),
+    NameAlreadyRegistered(AtomId),
+    NoSuchActor(ActorId),
+    NotPaused(ActorId),
+    NotInTunnel,
+    UnrecognizedChannel,
     NoSuchValue(Value),
     EnvNotInitialized(EnvId),
     EnvExportMismatch { expected: EnvId, found: EnvId, },
@@ -307,6 +616,16 @@ impl Default for StackFrame {
     }
 }
 
+impl Default for Heap {
+    fn default() -> Self {
+        Heap {
+            values: Vec::new(),
+            strings: StringInterner::new(),
+            maps: HashSet::new(),
+        }
+    }
+}
+
 macro_rules! index_via_u32 {
     ( $name:ident, $( $value:ty ),* ) => {
         #[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
@@ -375,6 +694,14 @@ impl Stack {
         &mut self.lower
     }
 
+    fn peek(&self) -> &StackFrame {
+        if let Some(c) = self.upper.as_ref() {
+            return &c.frame;
+        }
+
+        &self.lower
+    }
+
     fn push(&mut self, cc: Continuation) -> Ret<()> {
         if self.upper.is_some() {
             Err(RunErr::StackOverflow)
@@ -439,6 +766,16 @@ impl Heap {
         Ok(addr)
     }
 
+    fn alloc_str(&mut self, content: String) -> Value {
+        let addr = self.strings.get_or_intern(content);
+        Value::StrAddr(addr as u32)
+    }
+
+    fn str(&self, addr: u32) -> Ret<&str> {
+        self.strings.resolve(addr as usize)
+            .ok_or(RunErr::UnallocatedAccess(addr as usize))
+    }
+
     fn size_of(&self, addr: HeapAddr) -> Ret<u32> {
         let addr: usize = addr.into();
         let header = *self.values.get(addr)
@@ -470,20 +807,75 @@ impl Heap {
         Ok(())
     }
 
+    /// Reads the items of a list, skipping the first few.
+    fn items(&self, addr: HeapAddr, skip: u32) -> Ret<Vec<Value>> {
+        let len = self.size_of(addr)?;
+        let mut items = Vec::with_capacity(len.saturating_sub(skip) as usize);
+        for i in skip .. len {
+            items.push(self.get(addr, i)?);
+        }
+        Ok(items)
+    }
+
+    fn alloc_list(&mut self, items: Vec<Value>) -> Ret<Value> {
+        let addr = self.alloc(ListLen(items.len() as u32))?;
+        for (i, value) in items.into_iter().enumerate() {
+            self.set(addr, i as u32, value)?;
+        }
+        Ok(Value::ListAddr(addr))
+    }
+
+    /// Reads a block of alternating keys and values, such as a map.
+    fn entries(&self, addr: HeapAddr) -> Ret<Vec<(AtomId, Value)>> {
+        let len = self.size_of(addr)?;
+        let mut entries = Vec::with_capacity(len as usize / 2);
+        for i in 0 .. len / 2 {
+            let key = self.get(addr, 2 * i)?.as_atom()?;
+            let value = self.get(addr, 2 * i + 1)?;
+            entries.push((key, value));
+        }
+        Ok(entries)
+    }
+
+    /// Stores entries as a map. Later entries replace earlier ones with the
+    /// same key.
+    fn alloc_map(&mut self, entries: Vec<(AtomId, Value)>) -> Ret<Value> {
+        let mut sorted: Vec<(AtomId, Value)> = vec![];
+        for (key, value) in entries.into_iter() {
+            match sorted.binary_search_by_key(&key, |&(k, _)| k) {
+                Ok(i) => sorted[i].1 = value,
+                Err(i) => sorted.insert(i, (key, value)),
+            }
+        }
+
+        let addr = self.alloc(ListLen(2 * sorted.len() as u32))?;
+        for (i, (key, value)) in sorted.into_iter().enumerate() {
+            self.set(addr, 2 * i as u32, Value::Atom(key))?;
+            self.set(addr, 2 * i as u32 + 1, value)?;
+        }
+        self.maps.insert(addr);
+        Ok(Value::MapAddr(addr))
+    }
+
+    fn lookup(&self, map: HeapAddr, key: AtomId) -> Ret<Option<Value>> {
+        let entries = self.entries(map)?;
+        Ok(match entries.binary_search_by_key(&key, |&(k, _)| k) {
+            Ok(i) => Some(entries[i].1),
+            Err(_) => None,
+        })
+    }
+
     fn clear(&mut self) {
         self.values.clear();
         self.strings.clear();
+        self.maps.clear();
     }
 
     fn localize(&mut self, item: LocalValue) -> Ret<Value> {
         Ok(match item.value {
             Value::StrAddr(addr) => {
-                // FIXME: We should be using a StringInterner here
-                let len = self.strings.len();
-                let content = item.heap.strings.get(addr as usize)
-                    .ok_or(RunErr::UnallocatedAccess(addr as usize))?;
-                self.strings.push(content.to_owned());
-                Value::StrAddr(len as u32)
+                let content = item.heap.str(addr)?;
+                self.alloc_str(content.to_owned())
             },
 
             Value::ListAddr(addr) => {
@@ -499,13 +891,57 @@ impl Heap {
                 Value::ListAddr(list)
             },
 
+            Value::MapAddr(addr) => {
+                let mut entries = vec![];
+                for (key, value) in item.heap.entries(addr)? {
+                    entries.push((key, self.localize(LocalValue {
+                        value: value,
+                        heap: item.heap,
+                    })?));
+                }
+                self.alloc_map(entries)?
+            },
+
             other => other,
         })
     }
 }
 
 impl Process {
+    /// Reads a value as text, for splicing into a line.
+    fn text_of(&self, value: Value, program: &Program) -> Ret<String> {
+        match value {
+            Value::StrConst(id) => program.str(id).map(str::to_owned)
+                .ok_or(RunErr::NoSuchValue(value)),
+
+            Value::StrAddr(addr) => self.heap.str(addr).map(str::to_owned),
+
+            Value::Int(i) => Ok(i.to_string()),
+
+            other => Err(RunErr::TypeMismatch(other, TypeTag::Str)),
+        }
+    }
+
+    /// Compares two values the way `==` does.
+    fn same_value(&self, lhs: Value, rhs: Value, program: &Program) -> Ret<bool> {
+        // Strings are equal if they have the same content, wherever they're
+        // stored
+        match (lhs.tag(), rhs.tag()) {
+            (Ok(TypeTag::Str), Ok(TypeTag::Str)) => {
+                Ok(self.text_of(lhs, program)? == self.text_of(rhs, program)?)
+            },
+
+            _ => Ok(lhs == rhs),
+        }
+    }
+
     pub fn exec(&mut self, program: &Program) -> Ret<()> {
+        // Handlers run in an upper frame, so anything else has moved on
+        // from the wait they interrupted
+        if self.stack.upper.is_none() {
+            self.wake_at = None;
+        }
+
         match self.op {
             Instr::Nop => (),
 
@@ -548,10 +984,29 @@ impl Process {
             },
 
             Instr::Eql(lhs, rhs, flag) => {
-                let frame = self.stack.current();
-                let lhs = frame.get(lhs)?;
-                let rhs = frame.get(rhs)?;
-                frame.set_flag(flag, lhs == rhs)?;
+                let lhs = self.stack.current().get(lhs)?;
+                let rhs = self.stack.current().get(rhs)?;
+
+                // Other types are just unequal, but bools used to be ints,
+                // so comparing one with the other is always a mistake
+                match (lhs.tag()?, rhs.tag()?) {
+                    (TypeTag::Bool, TypeTag::Int) | (TypeTag::Int, TypeTag::Bool) => {
+                        return Err(RunErr::TypeMismatch(rhs, lhs.tag()?));
+                    },
+
+                    _ => (),
+                }
+
+                let equal = self.same_value(lhs, rhs, program)?;
+                self.stack.current().set_flag(flag, equal)?;
+            },
+
+            Instr::Same(lhs, rhs, flag) => {
+                let lhs = self.stack.current().get(lhs)?;
+                let rhs = self.stack.current().get(rhs)?;
+
+                let equal = self.same_value(lhs, rhs, program)?;
+                self.stack.current().set_flag(flag, equal)?;
             },
 
             Instr::Gte(lhs, rhs, flag) => {
@@ -615,7 +1070,13 @@ impl Process {
                 self.stack.current().set(dst, test.into())?;
             },
 
-            Instr::Nonzero(src, flag) => {
+            Instr::Group(src, dst) => {
+                let frame = self.stack.current();
+                let name = frame.get(src)?.as_atom()?;
+                frame.set(dst, Value::Group(name))?;
+            },
+
+            Instr::IsTrue(src, flag) => {
                 let value = self.stack.current().get(src)?;
                 self.stack.current().set_flag(flag, value.as_bool()?)?;
             },
@@ -638,6 +1099,37 @@ impl Process {
                 self.stack.current().set_flag(flag, result)?;
             },
 
+            Instr::CheckMinSize(ListLen(expected), reg, flag) => {
+                let result = match self.stack.current().get(reg)? {
+                    Value::ListAddr(addr) => {
+                        let found = self.heap.size_of(addr)?;
+                        found >= expected
+                    },
+
+                    _ => false,
+                };
+
+                self.stack.current().set_flag(flag, result)?;
+            },
+
+            Instr::CheckType(tag, reg, flag) => {
+                let value = self.stack.current().get(reg)?;
+                let result = value.tag().map(|t| t == tag).unwrap_or(false);
+                self.stack.current().set_flag(flag, result)?;
+            },
+
+            Instr::CheckKey(key, reg, flag) => {
+                let result = match self.stack.current().get(reg)? {
+                    Value::MapAddr(addr) => {
+                        self.heap.lookup(addr, key)?.is_some()
+                    },
+
+                    _ => false,
+                };
+
+                self.stack.current().set_flag(flag, result)?;
+            },
+
             Instr::LoadLit(value, dst) => {
                 self.stack.current().set(dst, value)?;
             },
@@ -661,6 +1153,155 @@ impl Process {
                 self.heap.set(addr, ptr.offset, value)?;
             },
 
+            Instr::Splice(src, dst) => {
+                let list = self.stack.current().get(src)?.as_addr()?;
+                let mut content = String::new();
+
+                for i in 0 .. self.heap.size_of(list)? {
+                    let value = self.heap.get(list, i)?;
+                    content.push_str(&self.text_of(value, program)?);
+                }
+
+                let value = self.heap.alloc_str(content);
+                self.stack.current().set(dst, value)?;
+            },
+
+            Instr::Format(src, dst) => {
+                let list = self.stack.current().get(src)?.as_addr()?;
+                let template = self.text_of(self.heap.get(list, 0)?, program)?;
+
+                let mut args = vec![];
+                for i in 1 .. self.heap.size_of(list)? {
+                    let value = self.heap.get(list, i)?;
+                    args.push(self.text_of(value, program)?);
+                }
+
+                let value = self.heap.alloc_str(fill_template(&template, &args));
+                self.stack.current().set(dst, value)?;
+            },
+
+            Instr::Len(src, dst) => {
+                let value = self.stack.current().get(src)?;
+
+                let len = match value.tag()? {
+                    TypeTag::Str => {
+                        self.text_of(value, program)?.chars().count() as u32
+                    },
+
+                    _ => self.heap.size_of(value.as_addr()?)?,
+                };
+
+                self.stack.current().set(dst, Value::Int(len as i32))?;
+            },
+
+            Instr::Concat(lhs, rhs, dst) => {
+                let lhs = self.stack.current().get(lhs)?;
+                let rhs = self.stack.current().get(rhs)?;
+
+                let mut content = self.text_of(lhs.as_str()?, program)?;
+                content.push_str(&self.text_of(rhs.as_str()?, program)?);
+
+                let value = self.heap.alloc_str(content);
+                self.stack.current().set(dst, value)?;
+            },
+
+            Instr::ToStr(src, dst) => {
+                let value = self.stack.current().get(src)?;
+
+                let content = match value {
+                    Value::Atom(id) => match program.atom_table.resolve(id) {
+                        Some(name) => name.to_owned(),
+                        None => return Err(RunErr::NoSuchAtom(id)),
+                    },
+
+                    other => self.text_of(other, program)?,
+                };
+
+                let value = self.heap.alloc_str(content);
+                self.stack.current().set(dst, value)?;
+            },
+
+            Instr::Member(item, list, dst) => {
+                let item = self.stack.current().get(item)?;
+                let list = self.stack.current().get(list)?.as_addr()?;
+
+                let mut found = false;
+                for other in self.heap.items(list, 0)? {
+                    if self.same_value(item, other, program)? {
+                        found = true;
+                        break;
+                    }
+                }
+
+                self.stack.current().set(dst, Value::Bool(found))?;
+            },
+
+            Instr::Append(lhs, rhs, dst) => {
+                let lhs = self.stack.current().get(lhs)?.as_addr()?;
+                let rhs = self.stack.current().get(rhs)?.as_addr()?;
+
+                let mut items = self.heap.items(lhs, 0)?;
+                items.extend(self.heap.items(rhs, 0)?);
+
+                let list = self.heap.alloc_list(items)?;
+                self.stack.current().set(dst, list)?;
+            },
+
+            Instr::Index(src, index, dst) => {
+                let list = self.stack.current().get(src)?.as_addr()?;
+                let index = self.stack.current().get(index)?.as_int()?;
+
+                if index < 0 {
+                    let addr = usize::from(list);
+                    return Err(RunErr::ListOutOfBounds(addr, index as u32));
+                }
+
+                let value = self.heap.get(list, index as u32)?;
+                self.stack.current().set(dst, value)?;
+            },
+
+            Instr::Drop(src, ListLen(n), dst) => {
+                let list = self.stack.current().get(src)?.as_addr()?;
+                let items = self.heap.items(list, n)?;
+                let list = self.heap.alloc_list(items)?;
+                self.stack.current().set(dst, list)?;
+            },
+
+            Instr::Map(src, dst) => {
+                let list = self.stack.current().get(src)?.as_addr()?;
+                let entries = self.heap.entries(list)?;
+                let map = self.heap.alloc_map(entries)?;
+                self.stack.current().set(dst, map)?;
+            },
+
+            Instr::Update(src, changes, dst) => {
+                let map = self.stack.current().get(src)?.as_map()?;
+                let changes = self.stack.current().get(changes)?.as_addr()?;
+
+                let mut entries = self.heap.entries(map)?;
+                entries.extend(self.heap.entries(changes)?);
+
+                let map = self.heap.alloc_map(entries)?;
+                self.stack.current().set(dst, map)?;
+            },
+
+            Instr::Field(src, key, dst) => {
+                let map = self.stack.current().get(src)?.as_map()?;
+                let value = self.heap.lookup(map, key)?;
+                let value = value.ok_or(RunErr::NoSuchKey(key))?;
+                self.stack.current().set(dst, value)?;
+            },
+
+            Instr::TryField(src, key, dst) => {
+                let value = match self.stack.current().get(src)? {
+                    Value::MapAddr(map) => self.heap.lookup(map, key)?,
+                    _ => None,
+                };
+
+                let value = value.unwrap_or(Value::Undefined);
+                self.stack.current().set(dst, value)?;
+            },
+
             Instr::Jump(label) => {
                 self.pc = *program.jump_table.get(label)?;
             },
@@ -687,6 +1328,21 @@ impl Process {
                 self.check_inbox(program)?;
             },
 
+            Instr::Unwind => {
+                let tunnel = self.tunnels.pop().ok_or(RunErr::NotInTunnel)?;
+                self.stack = tunnel.stack;
+                self.traps = tunnel.traps;
+                self.scene = tunnel.scene;
+                self.pc = tunnel.return_addr;
+
+                // Messages held for the caller came in first
+                let mut inbox = tunnel.inbox;
+                inbox.extend(self.inbox.drain(..));
+                self.inbox = inbox;
+
+                self.check_inbox(program)?;
+            },
+
             Instr::Arm(env, label) => {
                 self.arm(env, label)?;
             },
@@ -719,7 +1375,11 @@ impl Process {
         }
 
         if self.traps.is_empty() {
-            self.inbox.clear();
+            match self.tunnels.last_mut() {
+                Some(tunnel) => tunnel.inbox.extend(self.inbox.drain(..)),
+                None => self.inbox.clear(),
+            }
+
             return Ok(());
         }
 
@@ -728,6 +1388,7 @@ impl Process {
                 return_addr: self.pc,
                 queue: self.traps.clone(),
                 frame: StackFrame::default(),
+                label: Label::default(),
                 argv: argv,
             };
 
@@ -740,11 +1401,20 @@ impl Process {
     fn call(&mut self, mut cc: Continuation, program: &Program) -> Ret<()> {
         let trap = match cc.queue.pop() {
             Some(trap) => trap,
-            None => return Ok(()),
+
+            None => {
+                // Nothing took the message, but a caller still might
+                if let Some(tunnel) = self.tunnels.last_mut() {
+                    tunnel.inbox.push_back(cc.argv);
+                }
+
+                return Ok(());
+            },
         };
 
         cc.frame.set(Reg::env(), trap.env.into())?;
         cc.frame.set(Reg::arg(), cc.argv.into())?;
+        cc.label = trap.label;
 
         self.stack.push(cc)?;
         self.pc = *program.jump_table.get(trap.label)?;
@@ -752,6 +1422,28 @@ impl Process {
         Ok(())
     }
 
+    fn interrupt(&mut self, program: &Program) -> Ret<()> {
+        if self.stack.upper.is_some() || self.inbox.is_empty() {
+            return Ok(());
+        }
+
+        // The current instruction was fetched but hasn't been executed yet,
+        // so that's where the handlers should return to.
+        self.pc.0 -= 1;
+        self.resuming = false;
+        self.check_inbox(program)?;
+        self.fetch(program)
+    }
+
+    fn receive(&mut self, envelope: Envelope) -> Ret<()> {
+        let argv = self.heap.localize({
+            envelope.argv.in_heap(&envelope.heap)
+        })?;
+
+        self.inbox.push_back(argv.as_addr()?);
+        Ok(())
+    }
+
     pub fn fetch(&mut self, program: &Program) -> Ret<()> {
         self.op = *program.code.get(self.pc)?;
         self.pc.0 += 1;
@@ -775,17 +1467,68 @@ impl Process {
         self.stack.lower.set(Reg::env(), env)?;
 
         self.pc = *program.jump_table.get(label)?;
+        self.scene = label;
+        self.fetch(program)?;
+
+        Ok(())
+    }
+
+    /// Like `start()`, but the arguments are already on this heap.
+    fn enter(&mut self, argv: Value, env: LocalValue, label: Label, program: &Program) -> Ret<()> {
+        self.stack.lower.set(Reg::arg(), argv)?;
+
+        let env = self.heap.localize(env)?;
+        self.stack.lower.set(Reg::env(), env)?;
+
+        self.pc = *program.jump_table.get(label)?;
+        self.scene = label;
         self.fetch(program)?;
 
         Ok(())
     }
 
-    fn run(&mut self, program: &Program) -> Ret<RunState> {
+    /// Address of the instruction that was fetched but not yet executed.
+    fn addr(&self) -> Option<InstrAddr> {
+        self.pc.0.checked_sub(1).map(InstrAddr)
+    }
+
+    fn at_breakpoint(&mut self, breakpoints: &HashSet<InstrAddr>) -> bool {
+        if self.resuming {
+            self.resuming = false;
+            return false;
+        }
+
+        match self.addr() {
+            Some(addr) => breakpoints.contains(&addr),
+            None => false,
+        }
+    }
+
+    fn step(&mut self, program: &Program) -> Ret<RunState> {
+        self.interrupt(program)?;
+        self.resuming = false;
+
+        match self.run_state()? {
+            RunState::Running => (),
+            other => return Ok(other),
+        };
+
+        self.exec(program)?;
+        self.fetch(program)?;
+
+        Ok(RunState::Running)
+    }
+
+    fn run(&mut self, program: &Program, breakpoints: &HashSet<InstrAddr>) -> Ret<RunState> {
         const SOME_SMALL_NUMBER: usize = 100;
 
-        // FIXME: When do we check the inbox?
+        self.interrupt(program)?;
 
         for _ in 0 .. SOME_SMALL_NUMBER {
+            if self.at_breakpoint(breakpoints) {
+                return Ok(RunState::Break);
+            }
+
             match self.run_state()? {
                 RunState::Running => (),
                 other => return Ok(other),
@@ -801,6 +1544,55 @@ impl Process {
 }
 
 impl Program {
+    /// Looks up the script position of the instruction at `addr`.
+    pub fn source_of(&self, addr: InstrAddr) -> Option<&SourcePos> {
+        let i = match self.source_map.binary_search_by_key(&addr.0, |e| (e.0).0) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+
+        self.source_map.get(i).map(|&(_, ref pos)| pos)
+    }
+
+    /// Discards debugging information which isn't needed to run the program.
+    pub fn strip(&mut self) {
+        self.source_map = SourceMap::new();
+    }
+
+    /// Looks up a string constant, preferring its translation if one has
+    /// been loaded.
+    pub fn str(&self, id: StrId) -> Option<&str> {
+        match self.translations.get(&id) {
+            Some(text) => Some(text),
+            None => self.str_table.resolve(id),
+        }
+    }
+
+    /// Replaces string constants with their translations, keyed by the IDs
+    /// from `string_id()`. Strings missing from the table are left as they
+    /// were written, and so are those whose translation drops or invents a
+    /// `{N}` placeholder. Returns the IDs of the latter.
+    pub fn translate(&mut self, table: &HashMap<String, String>) -> Vec<String> {
+        let mut rejected = vec![];
+
+        self.translations.clear();
+
+        for (id, text) in self.str_table.iter() {
+            let key = string_id(text);
+
+            if let Some(translated) = table.get(&key) {
+                if placeholders(translated) == placeholders(text) {
+                    self.translations.insert(id, translated.clone());
+                } else {
+                    rejected.push(key);
+                }
+            }
+        }
+
+        rejected
+    }
+
     pub fn init(self) -> Ret<Scheduler> {
         let mut scheduler = Scheduler {
             program: self,
@@ -810,7 +1602,24 @@ impl Program {
                 sleeping: HashMap::new(),
                 dead: VecDeque::with_capacity(32),
             },
+            groups: ProcessGroups {
+                members: HashMap::new(),
+            },
+            debugger: Debugger {
+                paused: HashSet::new(),
+                breakpoints: HashSet::new(),
+            },
             global_heap: Heap::default(),
+            registry: HashMap::new(),
+            visits: HashMap::new(),
+            globals: HashMap::new(),
+            variations: HashMap::new(),
+            rng: XorShiftRng::from_seed(seed_words(0)),
+            clock: 0.0,
+            deadlines: HashMap::new(),
+            alarms: HashMap::new(),
+            arbiter: None,
+            mailbag: VecDeque::with_capacity(32),
             env_table: VecMap::with_capacity(32),
             outbuf: VecDeque::with_capacity(32),
             next_event: 0,
@@ -854,6 +1663,7 @@ impl Scheduler {
         task.process.stack.lower.set(Reg::env(), env)?;
 
         task.process.pc = *self.program.jump_table.get(label)?;
+        task.process.scene = label;
         task.process.fetch(&self.program)?;
 
         let id = task.id;
@@ -862,6 +1672,380 @@ impl Scheduler {
         Ok(id)
     }
 
+    /// Looks up the actor registered under the given name, if any.
+    pub fn whereis(&self, name: &str) -> Option<ActorId> {
+        let name = self.program.atom_table.get(name)?;
+        self.registry.get(&name).cloned()
+    }
+
+    /// Counts the visits to a scene (`"module:scene"`) or weave choice.
+    pub fn visits(&self, name: &str) -> u32 {
+        match self.program.atom_table.get(name) {
+            Some(key) => self.visits.get(&key).cloned().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// Reads a story variable, if it has been set.
+    pub fn global(&self, name: &str) -> Ret<Option<RawValue>> {
+        let value = match self.program.atom_table.get(name) {
+            Some(name) => self.globals.get(&name).cloned(),
+            None => None,
+        };
+
+        match value {
+            Some(value) => Ok(Some(self.marshal(value.in_heap(&self.global_heap))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Changes a story variable. Scripts see the new value the next time
+    /// they read it.
+    pub fn set_global(&mut self, name: &str, value: RawValue) -> Ret<()> {
+        let name = self.program.atom_table.get_or_intern(name);
+
+        let mut heap = ::std::mem::replace(&mut self.global_heap, {
+            Heap::default()
+        });
+
+        let value = self.unmarshal(value, &mut heap);
+        self.global_heap = heap;
+
+        self.globals.insert(name, value?);
+        Ok(())
+    }
+
+    /// Copies out the story state which should be kept in a saved game.
+    pub fn save(&self) -> Ret<SaveData> {
+        let mut globals = Vec::with_capacity(self.globals.len());
+
+        for (&name, &value) in self.globals.iter() {
+            let name = self.program.atom_table.resolve(name)
+                .ok_or(RunErr::NoSuchAtom(name))?;
+            let value = self.marshal(value.in_heap(&self.global_heap))?;
+            globals.push((name.to_owned(), value));
+        }
+
+        let mut visits = Vec::with_capacity(self.visits.len());
+
+        for (&key, &count) in self.visits.iter() {
+            let key = self.program.atom_table.resolve(key)
+                .ok_or(RunErr::NoSuchAtom(key))?;
+            visits.push((key.to_owned(), count));
+        }
+
+        Ok(SaveData {
+            globals: globals,
+            visits: visits,
+        })
+    }
+
+    /// Replaces the story state with one from a saved game. Running actors
+    /// are not affected.
+    pub fn restore(&mut self, data: SaveData) -> Ret<()> {
+        self.globals.clear();
+        self.visits.clear();
+
+        for (name, value) in data.globals.into_iter() {
+            self.set_global(&name, value)?;
+        }
+
+        for (key, count) in data.visits.into_iter() {
+            let key = self.program.atom_table.get_or_intern(key);
+            self.visits.insert(key, count);
+        }
+
+        Ok(())
+    }
+
+    /// Restarts the sequence of random choices made by shuffled variations.
+    /// Runs which use the same seed and receive the same input make the
+    /// same choices.
+    pub fn reseed(&mut self, seed: u32) {
+        self.rng.reseed(seed_words(seed));
+    }
+
+    /// Switches to translated text. See `Program::translate()`.
+    pub fn translate(&mut self, table: &HashMap<String, String>) -> Vec<String> {
+        self.program.translate(table)
+    }
+
+    /// Moves the story clock forward. Timed choices which run out, and
+    /// actors whose `wait` is over, are resolved on the next call to
+    /// `dispatch()`.
+    pub fn advance(&mut self, seconds: f32) {
+        self.clock += seconds;
+    }
+
+    /// Seconds of story time elapsed so far.
+    pub fn now(&self) -> f32 {
+        self.clock
+    }
+
+    /// Starts sorting lines into channels. Each channel delivers one line
+    /// at a time, and lines from busy channels hold up those with a lower
+    /// priority. Lines already delivered aren't affected.
+    pub fn arbitrate(&mut self, channels: Vec<Channel>) {
+        self.arbiter = Some(Arbiter {
+            channels: channels.into_iter().map(|config| ChannelQueue {
+                config: config,
+                speaking: None,
+                waiting: VecDeque::new(),
+            }).collect(),
+        });
+    }
+
+    /// Describes each dialogue channel, if lines are being arbitrated.
+    pub fn channels(&self) -> Vec<ChannelInfo> {
+        let arbiter = match self.arbiter {
+            Some(ref arbiter) => arbiter,
+            None => return vec![],
+        };
+
+        arbiter.channels.iter().map(|channel| ChannelInfo {
+            name: channel.config.name.clone(),
+            priority: channel.config.priority,
+            speaking: channel.speaking.as_ref().map(|tag| tag.0),
+            waiting: channel.waiting.iter().filter_map(|signal| {
+                ticket_of(signal).map(|tag| tag.0)
+            }).collect(),
+        }).collect()
+    }
+
+    /// Skips every line still waiting in a channel, as if the host had
+    /// already shown them. Questions are left alone. Returns the number of
+    /// lines skipped.
+    pub fn cancel(&mut self, channel: &str) -> Ret<usize> {
+        let skipped: Vec<OutSignal> = {
+            let arbiter = self.arbiter.as_mut()
+                .ok_or(RunErr::UnrecognizedChannel)?;
+            let channel = arbiter.channels.iter_mut()
+                .find(|c| c.config.name == channel)
+                .ok_or(RunErr::UnrecognizedChannel)?;
+
+            let (skipped, kept): (Vec<_>, Vec<_>) = {
+                channel.waiting.drain(..).partition(|s| match s {
+                    &OutSignal::Say(_) => true,
+                    _ => false,
+                })
+            };
+
+            channel.waiting = kept.into_iter().collect();
+            skipped
+        };
+
+        let count = skipped.len();
+
+        for signal in skipped {
+            if let OutSignal::Say(token) = signal {
+                self.write(token.reply().into());
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Adds an actor to the named group.
+    pub fn join(&mut self, group: &str, id: ActorId) -> Ret<()> {
+        let group = self.program.atom_table.get(group)
+            .ok_or(RunErr::UnrecognizedAtom)?;
+        self.groups.join(group, id);
+        Ok(())
+    }
+
+    /// Removes an actor from the named group.
+    pub fn leave(&mut self, group: &str, id: ActorId) -> Ret<()> {
+        let group = self.program.atom_table.get(group)
+            .ok_or(RunErr::UnrecognizedAtom)?;
+        self.groups.leave(group, id);
+        Ok(())
+    }
+
+    /// Lists the actors in the named group, in the order they joined.
+    pub fn members(&self, group: &str) -> Vec<ActorId> {
+        match self.program.atom_table.get(group) {
+            Some(group) => self.groups.members(group).to_vec(),
+            None => vec![],
+        }
+    }
+
+    /// Describes every live actor, ordered by actor ID.
+    pub fn actors(&self) -> Vec<ActorInfo> {
+        let mut ids: Vec<ActorId> = self.queue.running.keys()
+            .chain(self.queue.sleeping.keys())
+            .cloned()
+            .collect();
+
+        ids.sort_by_key(|&ActorId(id)| id);
+
+        ids.into_iter().filter_map(|id| self.inspect(id)).collect()
+    }
+
+    /// Describes a single live actor.
+    pub fn inspect(&self, id: ActorId) -> Option<ActorInfo> {
+        let process = self.process(id)?;
+
+        let lambda = process.stack.upper.as_ref().map(|cc| cc.label);
+
+        Some(ActorInfo {
+            id: id,
+            state: self.state(id),
+            scene: self.scene_name(process.scene),
+            lambda: lambda.and_then(|label| self.lambda_name(label)),
+            traps: process.traps.iter().filter_map(|trap| {
+                self.lambda_name(trap.label)
+            }).collect(),
+            inbox_len: process.inbox.len(),
+            heap_size: process.heap.values.len(),
+            pc: process.addr().unwrap_or(process.pc),
+        })
+    }
+
+    /// Reports what an actor is doing. Actors which have ended, or which
+    /// never existed, are reported as dead.
+    pub fn state(&self, id: ActorId) -> ActorState {
+        if self.queue.running.contains_key(&id) {
+            return ActorState::Running;
+        }
+
+        match self.queue.sleeping.get(&id) {
+            Some(&(_, ref process)) => match process.op {
+                Instr::Blocking(Io::ArmAtomic(_, _)) => ActorState::Listening,
+                Instr::Blocking(io) => ActorState::Sleeping(io),
+                _ => ActorState::Running,
+            },
+
+            None => ActorState::Dead,
+        }
+    }
+
+    /// Stops an actor from running until it is resumed or stepped.
+    pub fn pause(&mut self, id: ActorId) -> Ret<()> {
+        self.process(id).ok_or(RunErr::NoSuchActor(id))?;
+        self.debugger.paused.insert(id);
+        Ok(())
+    }
+
+    /// Lets a paused actor run again during the next dispatch.
+    pub fn resume(&mut self, id: ActorId) -> Ret<()> {
+        if !self.debugger.paused.remove(&id) {
+            return Err(RunErr::NotPaused(id));
+        }
+
+        Ok(())
+    }
+
+    /// Executes a single instruction on behalf of a paused actor. Does
+    /// nothing if the actor is waiting on the host.
+    pub fn step(&mut self, id: ActorId) -> Ret<()> {
+        if !self.debugger.paused.contains(&id) {
+            return Err(RunErr::NotPaused(id));
+        }
+
+        let mut process = match self.queue.running.remove(&id) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let status = process.step(&self.program);
+
+        self.workspace.push_back(Task {
+            id: id,
+            process: process,
+            status: status,
+        });
+
+        self.settle();
+        self.deliver_mail();
+
+        Ok(())
+    }
+
+    /// Stops any actor about to execute the instruction at `addr`.
+    pub fn set_breakpoint(&mut self, addr: InstrAddr) {
+        self.debugger.breakpoints.insert(addr);
+    }
+
+    pub fn clear_breakpoint(&mut self, addr: InstrAddr) {
+        self.debugger.breakpoints.remove(&addr);
+    }
+
+    /// Stops any actor entering the named scene. Returns the address of the
+    /// new breakpoint.
+    pub fn break_at_scene(&mut self, name: &str) -> Ret<InstrAddr> {
+        let label = self.program.scene_table.get(name)
+            .ok_or(RunErr::UnrecognizedSceneName)?.label;
+        let addr = *self.program.jump_table.get(label)?;
+        self.set_breakpoint(addr);
+        Ok(addr)
+    }
+
+    /// Lists the initialized registers in an actor's current stack frame.
+    pub fn registers(&self, id: ActorId) -> Ret<Vec<(Reg, RawValue)>> {
+        let process = self.process(id).ok_or(RunErr::NoSuchActor(id))?;
+        let frame = process.stack.peek();
+
+        let mut registers = vec![];
+
+        for (i, &value) in frame.gpr.iter().enumerate() {
+            if value == Value::Undefined {
+                continue;
+            }
+
+            let value = self.dump(value.in_heap(&process.heap))?;
+            registers.push((Reg(i as u32), value));
+        }
+
+        Ok(registers)
+    }
+
+    /// Lists every allocation in an actor's heap.
+    pub fn heap(&self, id: ActorId) -> Ret<Vec<(HeapAddr, RawValue)>> {
+        let process = self.process(id).ok_or(RunErr::NoSuchActor(id))?;
+        let heap = &process.heap;
+
+        let mut blocks = vec![];
+        let mut addr = HeapAddr(0);
+
+        while usize::from(addr) < heap.values.len() {
+            let value = if heap.maps.contains(&addr) {
+                Value::MapAddr(addr)
+            } else {
+                Value::ListAddr(addr)
+            };
+
+            blocks.push((addr, self.dump(value.in_heap(heap))?));
+            addr.0 += heap.size_of(addr)? + 1;
+        }
+
+        Ok(blocks)
+    }
+
+    fn locate(&self, process: &Process) -> Option<SourcePos> {
+        let addr = process.addr()?;
+        self.program.source_of(addr).cloned()
+    }
+
+    fn process(&self, id: ActorId) -> Option<&Process> {
+        match self.queue.running.get(&id) {
+            Some(p) => Some(p),
+            None => match self.queue.sleeping.get(&id) {
+                Some(&(_, ref p)) => Some(p),
+                None => None,
+            },
+        }
+    }
+
+    fn scene_name(&self, label: Label) -> Option<String> {
+        self.program.scene_table.iter()
+            .find(|&(_, def)| def.label == label)
+            .map(|(name, _)| name.clone())
+    }
+
+    fn lambda_name(&self, label: Label) -> Option<String> {
+        self.program.lambda_table.get(&label).cloned()
+    }
+
     pub fn write(&mut self, signal: InSignal) {
         match signal {
             InSignal::EndSay(SayReplyToken(ticket)) => {
@@ -873,8 +2057,35 @@ impl Scheduler {
                 }
             },
 
-            InSignal::EndAsk(AskReplyToken(ticket, index, dst)) => {
-                if let Some((id, mut process)) = self.wakeup(ticket) {
+            InSignal::EndAsk(AskReplyToken(ticket, index, dst, choices)) => {
+                let expired = match self.deadlines.get(&ticket.0) {
+                    Some(d) => d.ticket == ticket && d.expired(self.clock),
+                    None => false,
+                };
+
+                if expired {
+                    // Too late; the next dispatch takes the timeout arm
+                    return;
+                }
+
+                let locked = choices.iter().any(|c| {
+                    c.index == index && !c.available
+                });
+
+                let waiting = match self.queue.sleeping.get(&ticket.0) {
+                    Some(&(ref waiting_for, _)) => waiting_for == &ticket,
+                    None => false,
+                };
+
+                if locked && waiting {
+                    // The actor stays asleep until it gets a real answer
+                    let deadline = self.deadlines.get(&ticket.0)
+                        .filter(|d| d.ticket == ticket)
+                        .and_then(|d| d.at);
+                    let token = AskToken(ticket, choices, dst, deadline);
+                    self.outbuf.push_back(token.into());
+                } else if let Some((id, mut process)) = self.wakeup(ticket) {
+                    self.deadlines.remove(&id);
                     let _ = process.fetch(&self.program);
                     process.stack.current().set(dst, Value::Int(index))
                         .expect("Ask reply wrote to a bad register");
@@ -893,22 +2104,102 @@ impl Scheduler {
     pub fn dispatch(&mut self) {
         // FIXME: This isn't a very good scheduler.
 
-        self.workspace.extend(self.queue.running.drain().map(|(id, p)| {
-            Task {
-                id: id,
-                process: p,
-                status: Ok(RunState::Running),
+        self.expire_deadlines();
+        self.ring_alarms();
+
+        let ready: Vec<ActorId> = self.queue.running.keys()
+            .filter(|id| !self.debugger.paused.contains(id))
+            .cloned()
+            .collect();
+
+        for id in ready {
+            if let Some(p) = self.queue.running.remove(&id) {
+                self.workspace.push_back(Task {
+                    id: id,
+                    process: p,
+                    status: Ok(RunState::Running),
+                });
             }
-        }));
+        }
 
         for task in self.workspace.iter_mut() {
-            task.status = task.process.run(&self.program);
+            let breakpoints = &self.debugger.breakpoints;
+            task.status = task.process.run(&self.program, breakpoints);
+        }
+
+        self.settle();
+        self.deliver_mail();
+
+        let released = match self.arbiter {
+            Some(ref mut arbiter) => arbiter.release(),
+            None => vec![],
+        };
+
+        for signal in released {
+            self.deliver(signal);
         }
+    }
+
+    /// Answers timed questions on behalf of a host which took too long.
+    fn expire_deadlines(&mut self) {
+        let expired: Vec<ActorId> = self.deadlines.iter()
+            .filter(|&(_, deadline)| deadline.expired(self.clock))
+            .map(|(&id, _)| id)
+            .collect();
+
+        for id in expired {
+            let deadline = match self.deadlines.remove(&id) {
+                Some(deadline) => deadline,
+                None => continue,
+            };
+
+            if let Some((id, mut process)) = self.wakeup(deadline.ticket) {
+                let _ = process.fetch(&self.program);
+                process.stack.current().set(deadline.dst, {
+                    Value::Int(deadline.index)
+                }).expect("Ask timeout wrote to a bad register");
+                self.queue.running.insert(id, process);
+            }
+        }
+    }
 
+    /// Wakes actors which have waited long enough.
+    fn ring_alarms(&mut self) {
+        let ringing: Vec<ActorId> = self.alarms.iter()
+            .filter(|&(_, alarm)| alarm.at <= self.clock)
+            .map(|(&id, _)| id)
+            .collect();
+
+        for id in ringing {
+            let alarm = match self.alarms.remove(&id) {
+                Some(alarm) => alarm,
+                None => continue,
+            };
+
+            if let Some((id, mut process)) = self.wakeup(alarm.ticket) {
+                let _ = process.fetch(&self.program);
+                self.queue.running.insert(id, process);
+            }
+        }
+    }
+
+    fn settle(&mut self) {
         while let Some(mut task) = self.workspace.pop_front() {
+            if let Ok(RunState::Break) = task.status {
+                if let Some(addr) = task.process.addr() {
+                    self.outbuf.push_back(OutSignal::Break(task.id, addr));
+                }
+
+                task.process.resuming = true;
+                self.debugger.paused.insert(task.id);
+                self.queue.running.insert(task.id, task.process);
+                continue;
+            }
+
             if let Ok(RunState::Exiting) = task.status {
                 self.outbuf.push_back(OutSignal::Exit(task.id));
                 self.queue.dead.push_back(task.process);
+                self.reap(task.id);
                 continue;
             }
 
@@ -922,21 +2213,85 @@ impl Scheduler {
                 },
 
                 Err(err) => {
-                    self.outbuf.push_back(OutSignal::Hcf(task.id, err));
+                    let pos = self.locate(&task.process);
+                    self.outbuf.push_back(OutSignal::Hcf(task.id, err, pos));
                     self.queue.dead.push_back(task.process);
+                    self.reap(task.id);
                 },
             }
         }
     }
 
+    fn deliver_mail(&mut self) {
+        while let Some(envelope) = self.mailbag.pop_front() {
+            let id = envelope.to;
+
+            let result = if let Some(p) = self.queue.running.get_mut(&id) {
+                p.receive(envelope)
+            } else if let Some(&mut (_, ref mut p)) = self.queue.sleeping.get_mut(&id) {
+                p.receive(envelope)
+            } else {
+                // The recipient is dead, so the message is dropped
+                Ok(())
+            };
+
+            // A trap can cut a wait short; the wait carries on if the
+            // handler returns to it, and ends when it would have
+            let interrupted = match self.queue.sleeping.get(&id) {
+                Some(&(_, ref p)) => match p.op {
+                    Instr::Blocking(Io::Sleep(_)) => !p.traps.is_empty(),
+                    _ => false,
+                },
+
+                None => false,
+            };
+
+            if interrupted {
+                let alarm = self.alarms.remove(&id);
+
+                if let Some((_, mut process)) = self.queue.sleeping.remove(&id) {
+                    process.wake_at = alarm.map(|alarm| alarm.at);
+                    self.queue.running.insert(id, process);
+                }
+            }
+
+            if let Err(err) = result {
+                self.kill(id, err);
+            }
+        }
+    }
+
+    fn kill(&mut self, id: ActorId, err: RunErr) {
+        let process = match self.queue.running.remove(&id) {
+            Some(p) => p,
+            None => match self.queue.sleeping.remove(&id) {
+                Some((_, p)) => p,
+                None => return,
+            },
+        };
+
+        let pos = self.locate(&process);
+        self.outbuf.push_back(OutSignal::Hcf(id, err, pos));
+        self.queue.dead.push_back(process);
+        self.reap(id);
+    }
+
+    /// Releases everything held on behalf of an actor that has ended.
+    fn reap(&mut self, id: ActorId) {
+        self.registry.retain(|_, &mut owner| owner != id);
+        self.groups.remove(id);
+        self.debugger.paused.remove(&id);
+    }
+
     fn build_env(&mut self) -> Ret<()> {
         let mut init = Box::new(Process::default());
 
         loop {
-            let io = match init.run(&self.program)? {
+            let io = match init.run(&self.program, &HashSet::new())? {
                 RunState::Exiting => break,
                 RunState::Running => continue,
                 RunState::Blocked(io) => io,
+                RunState::Break => continue,
             };
 
             match io {
@@ -966,69 +2321,253 @@ impl Scheduler {
 
         self.queue.dead.push_back(init);
 
-        Ok(())
-    }
+        Ok(())
+    }
+
+    fn unblock(&mut self, task: &mut Task) -> Ret<Option<Tag>> {
+        let &mut Task { id, ref mut process, status } = task;
+
+        let io = match status? {
+            RunState::Blocked(io) => io,
+            RunState::Running => return Ok(None),
+            _ => return Err(RunErr::IllegalInstr(process.op)),
+        };
+
+        match io {
+            Io::Export(_, _) => {
+                return Err(RunErr::IllegalInstr(process.op))
+            },
+
+            Io::GetPid(dst) => {
+                let pid = Value::ActorId(id);
+                process.stack.current().set(dst, pid)?;
+                process.fetch(&self.program)?;
+                Ok(None)
+            },
+
+            Io::Say(msg, notes) => {
+                let value = process.stack.current().get(msg)?;
+                let content = self.marshal(value.in_heap(&process.heap))?;
+                let notes = process.stack.current().get(notes)?;
+                let notes = self.annotations(notes.in_heap(&process.heap))?;
+                let tag = self.tag(id);
+                let token = SayToken(tag.private_clone(), content, notes);
+                self.speak(token.into());
+                Ok(Some(tag))
+            },
+
+            Io::Ask(src, dst) => {
+                let value = process.stack.current().get(src)?;
+                let choices = self.get_menu(value.in_heap(&process.heap))?;
+
+                if choices.is_empty() {
+                    // Every choice has been used up or hidden, so don't
+                    // bother the host; no arm will match, and the last
+                    // resort runs. Locked choices are still shown, and
+                    // picking one asks again.
+                    process.stack.current().set(dst, Value::Int(-1))?;
+                    process.fetch(&self.program)?;
+                    return Ok(None);
+                }
+
+                let tag = self.tag(id);
+
+                let timer = self.get_timer(value.in_heap(&process.heap))?;
+                if let Some((index, seconds)) = timer {
+                    self.deadlines.insert(id, Deadline {
+                        at: None,
+                        seconds: seconds,
+                        ticket: tag.private_clone(),
+                        dst: dst,
+                        index: index,
+                    });
+                }
+
+                // The deadline is filled in when the host gets the question
+                let token = AskToken(tag.private_clone(), choices, dst, None);
+                self.speak(token.into());
+                Ok(Some(tag))
+            },
+
+            Io::ArmAtomic(env, label) => {
+                process.arm(env, label)?;
+                let tag = self.tag(id);
+                Ok(Some(tag))
+            },
+
+            Io::Native(_, _, _) => {
+                unimplemented!()
+            },
+
+            Io::Roll(src, dst) => {
+                let frame = process.stack.current();
+                let count = frame.get(dst)?.as_int()?;
+                let sides = frame.get(src)?.as_int()?;
+
+                if count < 0 || sides < 1 {
+                    return Err(RunErr::InvalidRoll {
+                        count: count,
+                        sides: sides,
+                    });
+                }
+
+                let mut total: i32 = 0;
+
+                for _ in 0 .. count {
+                    // Counting from zero keeps the range within an i32
+                    let roll = self.rng.gen_range(0, sides) + 1;
+
+                    total = total.checked_add(roll).ok_or(RunErr::RollOverflow {
+                        count: count,
+                        sides: sides,
+                    })?;
+                }
+
+                frame.set(dst, Value::Int(total))?;
+                process.fetch(&self.program)?;
+                Ok(None)
+            },
+
+            Io::Register(reg) => {
+                let name = process.stack.current().get(reg)?.as_atom()?;
+
+                match self.registry.get(&name) {
+                    Some(&owner) if owner != id => {
+                        return Err(RunErr::NameAlreadyRegistered(name));
+                    },
+
+                    _ => (),
+                }
+
+                self.registry.insert(name, id);
+                process.fetch(&self.program)?;
+                Ok(None)
+            },
+
+            Io::Visit(key) => {
+                *self.visits.entry(key).or_insert(0) += 1;
+                process.fetch(&self.program)?;
+                Ok(None)
+            },
+
+            Io::LoadGlobal(name, dst) => {
+                // Reading a variable before anyone sets it is a mistake
+                let value = match self.globals.get(&name) {
+                    Some(&value) => process.heap.localize({
+                        value.in_heap(&self.global_heap)
+                    })?,
+
+                    None => return Err(RunErr::UnsetGlobal(name)),
+                };
+
+                process.stack.current().set(dst, value)?;
+                process.fetch(&self.program)?;
+                Ok(None)
+            },
+
+            Io::StoreGlobal(name, src) => {
+                // FIXME: The old value is never freed
+                let value = self.global_heap.localize({
+                    process.stack.current().get(src)?.in_heap(&process.heap)
+                })?;
+
+                self.globals.insert(name, value);
+                process.fetch(&self.program)?;
+                Ok(None)
+            },
+
+            Io::CountVisits(key, dst) => {
+                let count = self.visits.get(&key).cloned().unwrap_or(0);
+                process.stack.current().set(dst, Value::Int(count as i32))?;
+                process.fetch(&self.program)?;
+                Ok(None)
+            },
+
+            Io::Vary(site, items, dst) => {
+                let count = if site.per_actor {
+                    process.variations.entry(site.key).or_insert(0)
+                } else {
+                    self.variations.entry(site.key).or_insert(0)
+                };
 
-    fn unblock(&mut self, task: &mut Task) -> Ret<Option<Tag>> {
-        let &mut Task { id, ref mut process, status } = task;
+                let n = *count;
+                *count += 1;
 
-        let io = match status? {
-            RunState::Blocked(io) => io,
-            RunState::Running => return Ok(None),
-            _ => return Err(RunErr::IllegalInstr(process.op)),
-        };
+                // One past the end is the empty string
+                let index = match site.kind {
+                    Variation::Sequence => n.min(site.len - 1),
+                    Variation::Cycle => n % site.len,
+                    Variation::Once => n.min(site.len),
+                    Variation::Shuffle => self.rng.gen_range(0, site.len),
+                };
 
-        match io {
-            Io::Export(_, _) => {
-                return Err(RunErr::IllegalInstr(process.op))
+                let frame = process.stack.current();
+                let items = frame.get(items)?.as_addr()?;
+                frame.set(dst, process.heap.get(items, index)?)?;
+                process.fetch(&self.program)?;
+                Ok(None)
             },
 
-            Io::GetPid(dst) => {
-                let pid = Value::ActorId(id);
-                process.stack.current().set(dst, pid)?;
+            Io::Join(reg) => {
+                let group = process.stack.current().get(reg)?.as_atom()?;
+                self.groups.join(group, id);
                 process.fetch(&self.program)?;
                 Ok(None)
             },
 
-            Io::Say(msg) => {
-                let value = process.stack.current().get(msg)?;
-                let content = self.marshal(value.in_heap(&process.heap))?;
-                let tag = self.tag(id);
-                let token = SayToken(tag.private_clone(), content);
-                self.outbuf.push_back(token.into());
-                Ok(Some(tag))
+            Io::Leave(reg) => {
+                let group = process.stack.current().get(reg)?.as_atom()?;
+                self.groups.leave(group, id);
+                process.fetch(&self.program)?;
+                Ok(None)
             },
 
-            Io::Ask(src, dst) => {
-                let value = process.stack.current().get(src)?;
-                let choices = self.get_menu(value.in_heap(&process.heap))?;
-                let tag = self.tag(id);
+            Io::SendMsg(msg, dst) => {
+                let recipients = match process.stack.current().get(dst)? {
+                    Value::ActorId(to) => vec![to],
 
-                let token = AskToken(tag.private_clone(), choices, dst);
-                self.outbuf.push_back(token.into());
-                Ok(Some(tag))
+                    Value::Atom(name) => match self.registry.get(&name) {
+                        Some(&to) => vec![to],
+                        None => return Err(RunErr::NoSuchName(name)),
+                    },
+
+                    Value::Group(name) => self.groups.members(name).to_vec(),
+
+                    other => {
+                        return Err(RunErr::TypeMismatch(other, TypeTag::Actor));
+                    },
+                };
+
+                let message = process.stack.current().get(msg)?;
+                for to in recipients {
+                    self.post(id, to, message.in_heap(&process.heap))?;
+                }
+
+                process.fetch(&self.program)?;
+                Ok(None)
             },
 
-            Io::ArmAtomic(env, label) => {
-                process.arm(env, label)?;
+            Io::Sleep(src) => {
+                let seconds = process.stack.current().get(src)?.as_int()?;
                 let tag = self.tag(id);
-                Ok(Some(tag))
-            },
 
-            Io::Native(_, _, _) => {
-                unimplemented!()
-            },
+                let at = match process.wake_at.take() {
+                    // The wait ran out while a handler was busy
+                    Some(at) if at <= self.clock => {
+                        process.fetch(&self.program)?;
+                        return Ok(None);
+                    },
 
-            Io::Roll(_, _) => {
-                unimplemented!()
-            },
+                    Some(at) => at,
+                    None => self.clock + seconds as f32,
+                };
 
-            Io::SendMsg(_, _) => {
-                unimplemented!()
-            },
+                self.alarms.insert(id, Alarm {
+                    at: at,
+                    ticket: tag.private_clone(),
+                });
 
-            Io::Sleep(_time) => {
-                unimplemented!()
+                Ok(Some(tag))
             },
 
             Io::Spawn(argv, label, dst) => {
@@ -1053,6 +2592,20 @@ impl Scheduler {
                 Ok(None)
             },
 
+            Io::Recur(argv, label) if !process.tunnels.is_empty() => {
+                // The callers' values are still on the heap, so keep it
+                let argv = process.stack.current().get(argv)?;
+                let &env_id = self.program.env_table.get(&label)
+                    .ok_or(RunErr::NoSuchScene(label))?;
+                let env = self.env_table.get(env_id)?
+                    .in_heap(&self.global_heap);
+
+                process.stack = Stack::default();
+                process.traps.clear();
+                process.enter(argv, env, label, &self.program)?;
+                Ok(None)
+            },
+
             Io::Recur(argv, label) => {
                 // Same as Spawn, but we replace the current process
                 let mut new = self.create();
@@ -1068,6 +2621,9 @@ impl Scheduler {
                     new.process.start(argv, env, label, &self.program)?;
                 }
 
+                // It's still the same actor, so it keeps count of how often
+                // it has said each variation, as it would in a tunnel
+                ::std::mem::swap(&mut process.variations, &mut new.process.variations);
                 ::std::mem::swap(process, &mut new.process);
 
                 self.queue.dead.push_back(new.process);
@@ -1075,10 +2631,34 @@ impl Scheduler {
                 Ok(None)
             },
 
+            Io::Tunnel(argv, label) => {
+                let argv = process.stack.current().get(argv)?;
+                let &env_id = self.program.env_table.get(&label)
+                    .ok_or(RunErr::NoSuchScene(label))?;
+                let env = self.env_table.get(env_id)?
+                    .in_heap(&self.global_heap);
+
+                process.tunnels.push(Tunnel {
+                    return_addr: process.pc,
+                    stack: ::std::mem::replace(&mut process.stack, {
+                        Stack::default()
+                    }),
+                    traps: ::std::mem::replace(&mut process.traps, vec![]),
+                    scene: process.scene,
+                    inbox: ::std::mem::replace(&mut process.inbox, {
+                        VecDeque::new()
+                    }),
+                });
+
+                process.enter(argv, env, label, &self.program)?;
+                Ok(None)
+            },
+
             Io::Trace(reg) => {
                 let value = process.stack.current().get(reg)?;
                 let message = self.marshal(value.in_heap(&process.heap))?;
-                self.outbuf.push_back(OutSignal::Trace(id, message));
+                let pos = self.locate(process);
+                self.outbuf.push_back(OutSignal::Trace(id, message, pos));
                 process.fetch(&self.program)?;
                 Ok(None)
             },
@@ -1092,6 +2672,10 @@ impl Scheduler {
         process.stack = Stack::default();
         process.heap.clear();
         process.traps.clear();
+        process.inbox.clear();
+        process.variations.clear();
+        process.tunnels.clear();
+        process.resuming = false;
 
         Task {
             id: new_id,
@@ -1100,15 +2684,74 @@ impl Scheduler {
         }
     }
 
+    fn post(&mut self, from: ActorId, to: ActorId, message: LocalValue) -> Ret<()> {
+        let mut heap = Heap::default();
+        let argv = heap.alloc(ListLen(2))?;
+        let message = heap.localize(message)?;
+        heap.set(argv, 0, message)?;
+        heap.set(argv, 1, from.into())?;
+
+        self.mailbag.push_back(Envelope {
+            to: to,
+            heap: heap,
+            argv: argv.into(),
+        });
+
+        Ok(())
+    }
+
     fn tag(&mut self, id: ActorId) -> Tag {
         let tag = Tag(id, self.next_event);
         self.next_event += 1;
         tag
     }
 
+    /// Passes a line on to the host, unless the arbiter wants to hold it.
+    fn speak(&mut self, signal: OutSignal) {
+        let signal = match self.arbiter {
+            Some(ref mut arbiter) => match arbiter.push(signal) {
+                Ok(()) => return,
+                Err(signal) => signal,
+            },
+
+            None => signal,
+        };
+
+        self.deliver(signal);
+    }
+
+    /// Hands a line to the host, starting the clock on a timed question.
+    fn deliver(&mut self, signal: OutSignal) {
+        let signal = match signal {
+            OutSignal::Ask(AskToken(ticket, choices, dst, _)) => {
+                let clock = self.clock;
+
+                let deadline = match self.deadlines.get_mut(&ticket.0) {
+                    Some(ref mut d) if d.ticket == ticket => {
+                        let at = clock + d.seconds;
+                        d.at = Some(at);
+                        Some(at)
+                    },
+
+                    _ => None,
+                };
+
+                OutSignal::Ask(AskToken(ticket, choices, dst, deadline))
+            },
+
+            other => other,
+        };
+
+        self.outbuf.push_back(signal);
+    }
+
     fn wakeup(&mut self, ticket: Tag) -> Option<(ActorId, Box<Process>)> {
         let id = ticket.0;
 
+        if let Some(ref mut arbiter) = self.arbiter {
+            arbiter.finish(&ticket);
+        }
+
         match self.queue.sleeping.remove(&id) {
             Some((waiting_for, process)) => {
                 if ticket == waiting_for {
@@ -1126,6 +2769,7 @@ impl Scheduler {
     fn marshal(&self, item: LocalValue) -> Ret<RawValue> {
         match item.value {
             Value::Int(i) => Ok(RawValue::Int(i)),
+            Value::Bool(b) => Ok(RawValue::Bool(b)),
             Value::ActorId(id) => Ok(RawValue::ActorId(id)),
 
             Value::Atom(id) => {
@@ -1135,15 +2779,19 @@ impl Scheduler {
                 }
             },
 
-            Value::StrAddr(addr) => {
-                match item.heap.strings.get(addr as usize) {
-                    Some(s) => Ok(RawValue::Str(s.clone())),
-                    None => Err(RunErr::UnallocatedAccess(addr as usize)),
+            Value::Group(id) => {
+                match self.program.atom_table.resolve(id) {
+                    Some(s) => Ok(RawValue::Group(s.to_owned())),
+                    None => Err(RunErr::NoSuchAtom(id)),
                 }
             },
 
+            Value::StrAddr(addr) => {
+                Ok(RawValue::Str(item.heap.str(addr)?.to_owned()))
+            },
+
             Value::StrConst(id) => {
-                match self.program.str_table.resolve(id) {
+                match self.program.str(id) {
                     Some(s) => Ok(RawValue::Str(s.to_owned())),
                     None => Err(RunErr::NoSuchValue(item.value)),
                 }
@@ -1159,17 +2807,63 @@ impl Scheduler {
                 Ok(RawValue::List(list))
             },
 
+            Value::MapAddr(addr) => {
+                let mut map = vec![];
+                for (key, value) in item.heap.entries(addr)? {
+                    let key = match self.program.atom_table.resolve(key) {
+                        Some(s) => s.to_owned(),
+                        None => return Err(RunErr::NoSuchAtom(key)),
+                    };
+
+                    map.push((key, self.marshal(value.in_heap(item.heap))?));
+                }
+                Ok(RawValue::Map(map))
+            },
+
             Value::Capacity(_) => Err(RunErr::HeapCorrupted(item.value)),
             Value::Undefined => Err(RunErr::Uninitialized),
         }
     }
 
+    /// Like `marshal`, but for the debugger, which can catch a list or map
+    /// before all of its slots have been written.
+    fn dump(&self, item: LocalValue) -> Ret<RawValue> {
+        match item.value {
+            Value::ListAddr(addr) => {
+                let mut list = vec![];
+                for value in item.heap.items(addr, 0)? {
+                    list.push(self.dump(value.in_heap(item.heap))?);
+                }
+                Ok(RawValue::List(list))
+            },
+
+            Value::MapAddr(addr) => {
+                let mut map = vec![];
+                for (key, value) in item.heap.entries(addr)? {
+                    let key = match self.program.atom_table.resolve(key) {
+                        Some(s) => s.to_owned(),
+                        None => return Err(RunErr::NoSuchAtom(key)),
+                    };
+
+                    map.push((key, self.dump(value.in_heap(item.heap))?));
+                }
+                Ok(RawValue::Map(map))
+            },
+
+            Value::Undefined => Ok(RawValue::Undefined),
+
+            _ => self.marshal(item),
+        }
+    }
+
     fn unmarshal(&self, item: RawValue, heap: &mut Heap) -> Ret<Value> {
         match item {
             RawValue::ActorId(a) => Ok(Value::ActorId(a)),
 
             RawValue::Int(i) => Ok(Value::Int(i)),
 
+            RawValue::Bool(b) => Ok(Value::Bool(b)),
+
             RawValue::Atom(name) => {
                 if let Some(id) = self.program.atom_table.get(name) {
                     Ok(Value::Atom(id))
@@ -1178,11 +2872,19 @@ impl Scheduler {
                 }
             },
 
+            RawValue::Group(name) => {
+                if let Some(id) = self.program.atom_table.get(name) {
+                    Ok(Value::Group(id))
+                } else {
+                    Err(RunErr::UnrecognizedAtom)
+                }
+            },
+
             RawValue::Str(s) => {
                 if let Some(id) = self.program.str_table.get(&s) {
                     Ok(Value::StrConst(id))
                 } else {
-                    unimplemented!()
+                    Ok(heap.alloc_str(s))
                 }
             },
 
@@ -1194,26 +2896,117 @@ impl Scheduler {
                 }
                 Ok(Value::ListAddr(addr))
             },
+
+            RawValue::Map(entries) => {
+                let mut map = Vec::with_capacity(entries.len());
+                for (key, value) in entries.into_iter() {
+                    let key = match self.program.atom_table.get(key) {
+                        Some(id) => id,
+                        None => return Err(RunErr::UnrecognizedAtom),
+                    };
+
+                    map.push((key, self.unmarshal(value, heap)?));
+                }
+                heap.alloc_map(map)
+            },
+
+            RawValue::Undefined => Err(RunErr::Uninitialized),
         }
     }
 
-    fn get_menu(&self, item: LocalValue) -> Ret<Vec<(i32, RawValue)>> {
+    fn get_menu(&self, item: LocalValue) -> Ret<Vec<Choice>> {
         let addr = item.value.as_addr()?;
         let len = item.heap.size_of(addr)?;
         let mut menu = Vec::with_capacity(len as usize);
         for i in 0 .. len {
             let choice_addr = item.heap.get(addr, i)?.as_addr()?;
+            if item.heap.size_of(choice_addr)? == 2 {
+                // Not a choice, but a timer
+                continue;
+            }
+
             let test = item.heap.get(choice_addr, 0)?.as_bool()?;
-            if test {
+
+            // Locked choices carry their own visibility and a reason
+            let (visible, reason) = if item.heap.size_of(choice_addr)? > 5 {
+                let visible = item.heap.get(choice_addr, 4)?.as_bool()?;
+                let reason = self.marshal({
+                    item.heap.get(choice_addr, 5)?.in_heap(item.heap)
+                })?;
+                (visible, reason)
+            } else {
+                (test, RawValue::Str(String::new()))
+            };
+
+            if visible {
                 let tag = item.heap.get(choice_addr, 1)?.as_int()?;
                 let title = self.marshal({
                     item.heap.get(choice_addr, 2)?.in_heap(item.heap)
                 })?;
-                menu.push((tag, title));
+                let tags = self.atom_names({
+                    item.heap.get(choice_addr, 3)?.in_heap(item.heap)
+                })?;
+                let reason = match reason {
+                    RawValue::Str(ref s) if test || s.is_empty() => None,
+                    other => Some(other),
+                };
+                menu.push(Choice {
+                    index: tag,
+                    text: title,
+                    tags: tags,
+                    available: test,
+                    reason: reason,
+                });
             }
         }
         Ok(menu)
     }
+
+    /// Finds the `[Index, Seconds]` timer in a menu, if it has one.
+    fn get_timer(&self, item: LocalValue) -> Ret<Option<(i32, f32)>> {
+        let addr = item.value.as_addr()?;
+        let len = item.heap.size_of(addr)?;
+        for i in 0 .. len {
+            let choice_addr = item.heap.get(addr, i)?.as_addr()?;
+            if item.heap.size_of(choice_addr)? == 2 {
+                let index = item.heap.get(choice_addr, 0)?.as_int()?;
+                let seconds = item.heap.get(choice_addr, 1)?.as_int()?;
+                return Ok(Some((index, seconds as f32)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the `[[Speaker], [Tags...]]` list attached to a line.
+    fn annotations(&self, item: LocalValue) -> Ret<Annotations> {
+        let addr = item.value.as_addr()?;
+
+        let speaker = self.atom_names({
+            item.heap.get(addr, 0)?.in_heap(item.heap)
+        })?.pop();
+
+        let tags = self.atom_names({
+            item.heap.get(addr, 1)?.in_heap(item.heap)
+        })?;
+
+        Ok(Annotations {
+            speaker: speaker,
+            tags: tags,
+        })
+    }
+
+    fn atom_names(&self, item: LocalValue) -> Ret<Vec<String>> {
+        let addr = item.value.as_addr()?;
+        let len = item.heap.size_of(addr)?;
+        let mut names = Vec::with_capacity(len as usize);
+        for i in 0 .. len {
+            let id = item.heap.get(addr, i)?.as_atom()?;
+            let name = self.program.atom_table.resolve(id)
+                .ok_or(RunErr::NoSuchAtom(id))?;
+            names.push(name.to_owned());
+        }
+        Ok(names)
+    }
 }
 
 impl RunQueue {
@@ -1226,23 +3019,75 @@ impl RunQueue {
     }
 }
 
+impl ProcessGroups {
+    fn join(&mut self, group: AtomId, id: ActorId) {
+        let members = self.members.entry(group).or_insert_with(Vec::new);
+        if !members.contains(&id) {
+            members.push(id);
+        }
+    }
+
+    fn leave(&mut self, group: AtomId, id: ActorId) {
+        if let Some(members) = self.members.get_mut(&group) {
+            members.retain(|&member| member != id);
+        }
+    }
+
+    fn remove(&mut self, id: ActorId) {
+        for members in self.members.values_mut() {
+            members.retain(|&member| member != id);
+        }
+
+        self.members.retain(|_, members| !members.is_empty());
+    }
+
+    fn members(&self, group: AtomId) -> &[ActorId] {
+        match self.members.get(&group) {
+            Some(members) => members,
+            None => &[],
+        }
+    }
+}
+
+impl Deadline {
+    fn expired(&self, clock: f32) -> bool {
+        match self.at {
+            Some(at) => at <= clock,
+            None => false,
+        }
+    }
+}
+
 impl SayToken {
     pub fn content(&self) -> String {
         String::from(self.1.clone())
     }
 
+    pub fn speaker(&self) -> Option<&str> {
+        self.2.speaker.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.2.tags
+    }
+
     pub fn reply(self) -> SayReplyToken {
         SayReplyToken(self.0)
     }
 }
 
 impl AskToken {
-    pub fn content(&self) -> &[(i32, RawValue)] {
+    pub fn content(&self) -> &[Choice] {
         &self.1
     }
 
+    /// Time on the story clock when the question answers itself, if ever.
+    pub fn deadline(&self) -> Option<f32> {
+        self.3
+    }
+
     pub fn reply(self, i: i32) -> AskReplyToken {
-        AskReplyToken(self.0, i, self.2)
+        AskReplyToken(self.0, i, self.2, self.1)
     }
 }
 
@@ -1260,10 +3105,13 @@ impl Value {
     pub fn tag(&self) -> Ret<TypeTag> {
         Ok(match self {
             &Value::Int(_) => TypeTag::Int,
+            &Value::Bool(_) => TypeTag::Bool,
             &Value::Atom(_) => TypeTag::Atom,
             &Value::ActorId(_) => TypeTag::Actor,
+            &Value::Group(_) => TypeTag::Group,
             &Value::StrConst(_) | &Value::StrAddr(_) => TypeTag::Str,
             &Value::ListAddr(_) | &Value::Capacity(_) => TypeTag::List,
+            &Value::MapAddr(_) => TypeTag::Map,
             &Value::Undefined => return Err(RunErr::Uninitialized),
         })
     }
@@ -1275,8 +3123,18 @@ impl Value {
         }
     }
 
+    pub fn as_atom(self) -> Ret<AtomId> {
+        match self {
+            Value::Atom(a) => Ok(a),
+            _ => Err(RunErr::TypeMismatch(self, TypeTag::Atom)),
+        }
+    }
+
     pub fn as_bool(self) -> Ret<bool> {
-        Ok(self.as_int()? != 0)
+        match self {
+            Value::Bool(b) => Ok(b),
+            _ => Err(RunErr::TypeMismatch(self, TypeTag::Bool)),
+        }
     }
 
     fn as_addr(self) -> Ret<HeapAddr> {
@@ -1286,6 +3144,20 @@ impl Value {
         }
     }
 
+    fn as_str(self) -> Ret<Self> {
+        match self {
+            Value::StrConst(_) | Value::StrAddr(_) => Ok(self),
+            _ => Err(RunErr::TypeMismatch(self, TypeTag::Str)),
+        }
+    }
+
+    fn as_map(self) -> Ret<HeapAddr> {
+        match self {
+            Value::MapAddr(addr) => Ok(addr),
+            _ => Err(RunErr::TypeMismatch(self, TypeTag::Map)),
+        }
+    }
+
     fn in_heap<'a>(self, heap: &'a Heap) -> LocalValue<'a> {
         LocalValue {
             value: self,
@@ -1308,7 +3180,7 @@ impl From<i32> for Value {
 
 impl From<bool> for Value {
     fn from(b: bool) -> Self {
-        Value::Int(if b { 1 } else { 0 })
+        Value::Bool(b)
     }
 }
 
@@ -1381,6 +3253,89 @@ impl From<AskToken> for OutSignal {
     }
 }
 
+impl Arbiter {
+    /// Queues a line or question on its channel. Hands it back if there are
+    /// no channels to put it on.
+    fn push(&mut self, signal: OutSignal) -> Result<(), OutSignal> {
+        let index = {
+            let tags: Vec<&String> = match &signal {
+                &OutSignal::Say(ref token) => token.tags().iter().collect(),
+
+                &OutSignal::Ask(ref token) => token.content().iter()
+                    .flat_map(|choice| choice.tags.iter())
+                    .collect(),
+
+                _ => vec![],
+            };
+
+            self.channels.iter()
+                .position(|c| tags.contains(&&c.config.name))
+                .unwrap_or(0)
+        };
+
+        match self.channels.get_mut(index) {
+            Some(channel) => {
+                channel.waiting.push_back(signal);
+                Ok(())
+            },
+
+            None => Err(signal),
+        }
+    }
+
+    /// Forgets a line once its actor has moved on.
+    fn finish(&mut self, ticket: &Tag) {
+        for channel in self.channels.iter_mut() {
+            if channel.speaking.as_ref() == Some(ticket) {
+                channel.speaking = None;
+            }
+
+            channel.waiting.retain(|signal| ticket_of(signal) != Some(ticket));
+        }
+    }
+
+    /// Takes the next line from each channel which is free to speak.
+    fn release(&mut self) -> Vec<OutSignal> {
+        let mut released = vec![];
+        let mut order: Vec<usize> = (0 .. self.channels.len()).collect();
+        order.sort_by_key(|&i| Reverse(self.channels[i].config.priority));
+
+        let mut busy_above = None;
+
+        for i in order {
+            let channel = &mut self.channels[i];
+
+            if let Some(priority) = busy_above {
+                if priority > channel.config.priority {
+                    continue;
+                }
+            }
+
+            if channel.speaking.is_none() {
+                if let Some(signal) = channel.waiting.pop_front() {
+                    channel.speaking = ticket_of(&signal)
+                        .map(|tag| tag.private_clone());
+                    released.push(signal);
+                }
+            }
+
+            if channel.speaking.is_some() || !channel.waiting.is_empty() {
+                busy_above = Some(channel.config.priority);
+            }
+        }
+
+        released
+    }
+}
+
+fn ticket_of(signal: &OutSignal) -> Option<&Tag> {
+    match signal {
+        &OutSignal::Say(SayToken(ref tag, _, _)) => Some(tag),
+        &OutSignal::Ask(AskToken(ref tag, _, _, _)) => Some(tag),
+        _ => None,
+    }
+}
+
 impl Default for Stack {
     fn default() -> Self {
         Stack {
@@ -1390,6 +3345,88 @@ impl Default for Stack {
     }
 }
 
+/// Names a string constant in a way which stays the same from one build to
+/// the next, as long as the text itself doesn't change.
+pub fn string_id(text: &str) -> String {
+    // 64-bit FNV-1a
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for &byte in text.as_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    format!("{:016x}", hash)
+}
+
+/// Lists the `{N}` placeholders in a template, in numeric order.
+fn placeholders(template: &str) -> Vec<usize> {
+    let mut found = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1 ..];
+
+        if let Some(end) = rest.find('}') {
+            if let Ok(i) = rest[.. end].parse::<usize>() {
+                found.push(i);
+            }
+        }
+    }
+
+    found.sort();
+    found
+}
+
+/// Replaces each `{N}` in a template with the Nth argument. Braces around
+/// anything else are left alone.
+fn fill_template(template: &str, args: &[String]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[.. start]);
+        rest = &rest[start ..];
+
+        let arg = rest.find('}').and_then(|end| {
+            rest[1 .. end].parse::<usize>().ok()
+                .and_then(|i| args.get(i))
+                .map(|arg| (arg, end))
+        });
+
+        match arg {
+            Some((arg, end)) => {
+                output.push_str(arg);
+                rest = &rest[end + 1 ..];
+
+                // Close the gap left by a variation with nothing to say
+                if arg.is_empty() {
+                    if output.is_empty() || output.ends_with(' ') {
+                        if rest.starts_with(' ') {
+                            rest = &rest[1 ..];
+                        } else if rest.is_empty() {
+                            output.pop();
+                        }
+                    }
+                }
+            },
+
+            None => {
+                output.push('{');
+                rest = &rest[1 ..];
+            },
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn seed_words(seed: u32) -> [u32; 4] {
+    // XorShift can't start from all zeroes
+    [seed, 0x193a6754, 0xa8a7d469, 0x97830e05]
+}
+
 impl Default for Process {
     fn default() -> Self {
         Process {
@@ -1399,6 +3436,11 @@ impl Default for Process {
             inbox: VecDeque::with_capacity(8),
             op: Instr::Nop,
             pc: InstrAddr(0),
+            scene: Label(0),
+            variations: HashMap::new(),
+            tunnels: vec![],
+            resuming: false,
+            wake_at: None,
         }
     }
 }
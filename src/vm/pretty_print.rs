@@ -14,6 +14,13 @@ impl From<RawValue> for String {
                     .join(" ")
             },
 
+            RawValue::Map(entries) => {
+                entries.into_iter()
+                    .map(|(_, value)| String::from(value))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            },
+
             other => format!("{}", other),
         }
     }
@@ -26,15 +33,27 @@ impl Display for RawValue {
 
             &RawValue::Int(i) => write!(f, "{}", i),
 
+            &RawValue::Bool(b) => write!(f, "{}", b),
+
             &RawValue::Str(ref s) => write!(f, "> {}", s),
 
             &RawValue::Atom(ref a) => write!(f, "#{}", a),
 
+            &RawValue::Group(ref g) => write!(f, "group #{}", g),
+
+            &RawValue::Undefined => write!(f, "UNDEF"),
+
             &RawValue::List(ref values) => {
                 write!(f, "[{}]", values.iter().map(|value| {
                     format!("{}", value)
                 }).collect::<Vec<_>>().join(", "))
             },
+
+            &RawValue::Map(ref entries) => {
+                write!(f, "{{{}}}", entries.iter().map(|&(ref key, ref value)| {
+                    format!("{}: {}", key, value)
+                }).collect::<Vec<_>>().join(", "))
+            },
         }
     }
 }
@@ -45,6 +64,10 @@ impl Display for Program {
             writeln!(f, "scene {}/{}:\t{}", name, def.argc, def.label)?;
         }
 
+        for (label, name) in self.lambda_table.iter() {
+            writeln!(f, "trap {}:\t{}", name, label)?;
+        }
+
         let mut pairs: Vec<_> = self.jump_table.iter()
             .map(|(k, v)| (v.clone(), k))
             .collect();
@@ -54,6 +77,8 @@ impl Display for Program {
 
         let mut label = pairs.pop();
 
+        let mut positions = self.source_map.iter().peekable();
+
         for (line, instr) in self.code.iter() {
             match label {
                 Some((addr, name)) if line == addr => {
@@ -64,6 +89,12 @@ impl Display for Program {
                 _ => (),
             }
 
+            while let Some(&&(addr, ref pos)) = positions.peek() {
+                if addr != line { break; }
+                writeln!(f, "\t-- {}", pos)?;
+                positions.next();
+            }
+
             writeln!(f, "\t{}", instr)?;
         }
 
@@ -84,6 +115,7 @@ impl Display for Instr {
             &Instr::Nop => write!(f, "nop"),
 
             &Instr::Return(result) => write!(f, "ret {}", result),
+            &Instr::Unwind => write!(f, "unwind"),
             &Instr::Arm(reg, label) => write!(f, "arm {}, {}", reg, label),
             &Instr::Disarm(label) => write!(f, "dis {}", label),
 
@@ -91,6 +123,45 @@ impl Display for Instr {
             &Instr::Cpy(src, dst) => write!(f, "let {} -> {}", src, dst),
             &Instr::Read(src, dst) => write!(f, "let {} -> {}", src, dst),
             &Instr::Write(src, dst) => write!(f, "let {} -> {}", src, dst),
+            &Instr::Splice(src, dst) => write!(f, "splice {} -> {}", src, dst),
+            &Instr::Format(src, dst) => write!(f, "format {} -> {}", src, dst),
+            &Instr::Len(src, dst) => write!(f, "len {} -> {}", src, dst),
+
+            &Instr::Concat(lhs, rhs, dst) => {
+                write!(f, "concat {}, {} -> {}", lhs, rhs, dst)
+            },
+
+            &Instr::ToStr(src, dst) => write!(f, "str {} -> {}", src, dst),
+
+            &Instr::Member(item, list, dst) => {
+                write!(f, "member {}, {} -> {}", item, list, dst)
+            },
+
+            &Instr::Append(lhs, rhs, dst) => {
+                write!(f, "append {}, {} -> {}", lhs, rhs, dst)
+            },
+
+            &Instr::Index(src, index, dst) => {
+                write!(f, "let {}[{}] -> {}", src, index, dst)
+            },
+
+            &Instr::Drop(src, ListLen(n), dst) => {
+                write!(f, "drop {} from {} -> {}", n, src, dst)
+            },
+
+            &Instr::Map(src, dst) => write!(f, "map {} -> {}", src, dst),
+
+            &Instr::Update(src, changes, dst) => {
+                write!(f, "update {} with {} -> {}", src, changes, dst)
+            },
+
+            &Instr::Field(src, AtomId(key), dst) => {
+                write!(f, "let {}.#{} -> {}", src, key, dst)
+            },
+
+            &Instr::TryField(src, AtomId(key), dst) => {
+                write!(f, "let {}.#{}? -> {}", src, key, dst)
+            },
 
             &Instr::Add(src, dst) => write!(f, "add {} -> {}", src, dst),
             &Instr::Sub(src, dst) => write!(f, "sub {} -> {}", src, dst),
@@ -101,14 +172,22 @@ impl Display for Instr {
 
             &Instr::Not(dst) => write!(f, "not {}", dst),
 
-            &Instr::Nonzero(src, dst) => {
-                write!(f, "test nonzero {} -> {}", src, dst)
+            &Instr::Group(src, dst) => {
+                write!(f, "let group({}) -> {}", src, dst)
+            },
+
+            &Instr::IsTrue(src, dst) => {
+                write!(f, "test true {} -> {}", src, dst)
             },
 
             &Instr::Eql(lhs, rhs, dst) => {
                 write!(f, "test {} eq {} -> {}", lhs, rhs, dst)
             },
 
+            &Instr::Same(lhs, rhs, dst) => {
+                write!(f, "test {} same {} -> {}", lhs, rhs, dst)
+            },
+
             &Instr::Gt(lhs, rhs, dst) => {
                 write!(f, "test {} gt {} -> {}", lhs, rhs, dst)
             },
@@ -129,8 +208,20 @@ impl Display for Instr {
                 write!(f, "test len({}) eq {} -> {}", src, len, dst)
             },
 
+            &Instr::CheckMinSize(ListLen(len), src, dst) => {
+                write!(f, "test len({}) gte {} -> {}", src, len, dst)
+            },
+
+            &Instr::CheckKey(AtomId(key), src, dst) => {
+                write!(f, "test has({}, #{}) -> {}", src, key, dst)
+            },
+
+            &Instr::CheckType(tag, src, dst) => {
+                write!(f, "test is({}, {:?}) -> {}", src, tag, dst)
+            },
+
             &Instr::Reify(src, dst) => {
-                write!(f, "let int({}) -> {}", src, dst)
+                write!(f, "let bool({}) -> {}", src, dst)
             },
 
             &Instr::And(src, dst) => write!(f, "and {} -> {}", src, dst),
@@ -164,6 +255,10 @@ impl Display for Instr {
                     write!(f, "recur {}, {}", arg, label)
                 },
 
+                Io::Tunnel(arg, label) => {
+                    write!(f, "tunnel {}, {}", arg, label)
+                },
+
                 Io::GetPid(dst) => {
                     write!(f, "self -> {}", dst)
                 },
@@ -176,12 +271,45 @@ impl Display for Instr {
                     write!(f, "roll {} -> {}", src, dst)
                 },
 
-                Io::Sleep(amt) => {
-                    write!(f, "sleep {}", amt)
+                Io::Sleep(src) => {
+                    write!(f, "sleep {}", src)
+                },
+
+                Io::Register(src) => {
+                    write!(f, "register {}", src)
+                },
+
+                Io::Visit(AtomId(key)) => {
+                    write!(f, "visit #{}", key)
+                },
+
+                Io::LoadGlobal(AtomId(name), dst) => {
+                    write!(f, "let global(#{}) -> {}", name, dst)
+                },
+
+                Io::StoreGlobal(AtomId(name), src) => {
+                    write!(f, "let {} -> global(#{})", src, name)
                 },
 
-                Io::Say(src) => {
-                    write!(f, "say {}", src)
+                Io::CountVisits(AtomId(key), dst) => {
+                    write!(f, "let visits(#{}) -> {}", key, dst)
+                },
+
+                Io::Vary(site, src, dst) => {
+                    let AtomId(key) = site.key;
+                    write!(f, "let vary(#{}, {}) -> {}", key, src, dst)
+                },
+
+                Io::Join(src) => {
+                    write!(f, "join {}", src)
+                },
+
+                Io::Leave(src) => {
+                    write!(f, "leave {}", src)
+                },
+
+                Io::Say(src, notes) => {
+                    write!(f, "say {}, {}", src, notes)
                 },
 
                 Io::Native(arg, NativeFn(func), dst) => {
@@ -218,11 +346,14 @@ impl Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             &Value::Int(i) => write!(f, "{}i", i),
+            &Value::Bool(b) => write!(f, "{}", b),
             &Value::Atom(AtomId(a)) => write!(f, "#{}", a),
             &Value::ActorId(ActorId(a)) => write!(f, "&PID({})", a),
+            &Value::Group(AtomId(g)) => write!(f, "&GROUP(#{})", g),
             &Value::StrConst(StrId(s)) => write!(f, "&STR({})", s),
             &Value::StrAddr(s) => write!(f, "&DYN({})", s),
             &Value::ListAddr(HeapAddr(h)) => write!(f, ".{:X}", h),
+            &Value::MapAddr(HeapAddr(h)) => write!(f, "{{.{:X}}}", h),
             &Value::Capacity(c) => write!(f, "0x{:X}", c),
             &Value::Undefined => write!(f, "UNDEF"),
         }
@@ -242,6 +373,21 @@ impl Display for Ptr {
     }
 }
 
+impl Display for SourcePos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.scene {
+            Some(ref scene) => write!(f, "{}, line {}", scene, self.line),
+            None => write!(f, "{} prelude, line {}", self.module, self.line),
+        }
+    }
+}
+
+impl Display for InstrAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{:04X}", self.0)
+    }
+}
+
 impl Display for Label {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "'{:X}", self.0)
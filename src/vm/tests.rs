@@ -0,0 +1,745 @@
+//! Whole scripts run on a scheduler, checked by what the host sees.
+
+use ast::{self, Modpath, Module};
+
+use super::*;
+
+fn compile(source: &str) -> Program {
+    let program = ast::Program {
+        modules: vec![
+            (Modpath(vec!["test".to_owned()]), Module::parse(source).unwrap()),
+        ],
+    };
+
+    program.compile().unwrap()
+}
+
+fn boot(source: &str) -> Scheduler {
+    compile(source).init().unwrap()
+}
+
+/// Runs the story for some one second rounds, noting down every signal the
+/// host receives. Lines are answered straight away, and questions with the
+/// first choice that can be picked.
+fn run(vm: &mut Scheduler, rounds: u32) -> Vec<String> {
+    run_with(vm, rounds, |choices| {
+        choices.iter().find(|choice| choice.available).map(|choice| choice.index)
+    })
+}
+
+/// Like `run`, but questions are answered by `pick`, or left for their
+/// deadline if it returns None.
+fn run_with<F>(vm: &mut Scheduler, rounds: u32, mut pick: F) -> Vec<String>
+    where F: FnMut(&[Choice]) -> Option<i32>
+{
+    let mut log = vec![];
+
+    for _ in 0..rounds {
+        vm.advance(1.0);
+        vm.dispatch();
+
+        while let Some(signal) = vm.read() {
+            match signal {
+                OutSignal::Exit(ActorId(id)) => log.push(format!("exit {}", id)),
+
+                OutSignal::Hcf(ActorId(id), err, pos) => {
+                    let pos = pos.map(|pos| pos.to_string()).unwrap_or_default();
+                    log.push(format!("hcf {}: {:?} at {}", id, err, pos));
+                },
+
+                OutSignal::Trace(ActorId(id), value, _) => {
+                    log.push(format!("trace {}: {}", id, value));
+                },
+
+                OutSignal::Break(id, addr) => {
+                    log.push(format!("break {}: {}", id.0, addr));
+                    vm.resume(id).unwrap();
+                },
+
+                OutSignal::Say(token) => {
+                    log.push(token.content());
+                    vm.write(token.reply().into());
+                },
+
+                OutSignal::Ask(token) => {
+                    let choices = token.content().iter().map(|choice| {
+                        if choice.available {
+                            format!("{}", choice.text)
+                        } else {
+                            format!("({})", choice.text)
+                        }
+                    }).collect::<Vec<_>>();
+
+                    log.push(format!("ask {}", choices.join(" | ")));
+
+                    if let Some(i) = pick(token.content()) {
+                        vm.write(token.reply(i).into());
+                    }
+                },
+            }
+        }
+    }
+
+    log
+}
+
+#[test]
+fn deliver_by_registered_name() {
+    let mut vm = boot(include_str!("../../tests/valid/registry.svr"));
+    let narrator = vm.spawn("test:start", vec![]).unwrap();
+
+    let log = run(&mut vm, 8);
+    assert_eq!(vm.whereis("narrator"), Some(narrator));
+    assert!(log.contains(&"trace 1: ?PID(0)".to_owned()), "{:?}", log);
+
+    let mut log = run(&mut vm, 10);
+    log.sort();
+    assert_eq!(log, vec!["exit 0", "exit 1"]);
+    assert_eq!(vm.whereis("narrator"), None);
+}
+
+#[test]
+fn deliver_to_group() {
+    let mut vm = boot(include_str!("../../tests/valid/groups.svr"));
+    vm.spawn("test:start", vec![]).unwrap();
+
+    let log = run(&mut vm, 8);
+    assert!(log.contains(&"trace 1: ?PID(0)".to_owned()), "{:?}", log);
+    assert!(log.contains(&"trace 2: ?PID(0)".to_owned()), "{:?}", log);
+
+    // Every listener left the group when it heard the cheer
+    assert!(vm.members("crowd").is_empty());
+}
+
+#[test]
+fn inspect_live_actors() {
+    let mut vm = boot("== start\n\ntrap\n| #poke\n    trace #poked\n;;\n\nwait 5\n");
+    let actor = vm.spawn("test:start", vec![]).unwrap();
+
+    run(&mut vm, 3);
+
+    let info = vm.inspect(actor).unwrap();
+    assert_eq!(info.scene, Some("test:start".to_owned()));
+    assert_eq!(info.traps, vec!["test:start'anonymous_label%0"]);
+    assert_eq!(info.lambda, None);
+
+    match info.state {
+        ActorState::Sleeping(Io::Sleep(_)) => (),
+        other => panic!("Expected the actor to be waiting, not {:?}", other),
+    }
+
+    assert_eq!(vm.actors().len(), 1);
+
+    run(&mut vm, 10);
+    assert!(vm.inspect(actor).is_none());
+    assert!(vm.actors().is_empty());
+
+    match vm.state(actor) {
+        ActorState::Dead => (),
+        other => panic!("Expected the actor to be dead, not {:?}", other),
+    }
+}
+
+#[test]
+fn keep_deadline_of_interrupted_waits() {
+    let source = "== start\n\ntrap\n| #tick\n    trace #tick\n;;\n\nspawn noise(Self)\nwait 10\ntrace #done\n\n== noise(Dst)\n\nwait 3\nDst <- #noise\nwait 3\nDst <- #noise\n";
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    // Messages cut the wait short, but it still ends on time
+    let log = run(&mut vm, 16);
+    assert!(log.contains(&"trace 0: #done".to_owned()), "{:?}", log);
+}
+
+#[test]
+fn dump_actor_at_breakpoint() {
+    let mut vm = boot("== start\n\nlet Stats = {str: 3, dex: 2}\nlet Items = [Stats, 5]\n\ntrace Items\n");
+    let entry = vm.break_at_scene("test:start").unwrap();
+    assert!(vm.break_at_scene("test:nowhere").is_err());
+
+    let actor = vm.spawn("test:start", vec![]).unwrap();
+
+    let mut stops = vec![];
+    for _ in 0..3 {
+        vm.dispatch();
+        while let Some(signal) = vm.read() {
+            if let OutSignal::Break(id, addr) = signal {
+                stops.push((id, addr));
+            }
+        }
+    }
+
+    assert_eq!(stops, vec![(actor, entry)]);
+
+    // Stop halfway through building the list
+    for _ in 0..14 {
+        vm.step(actor).unwrap();
+    }
+
+    let registers = vm.registers(actor).unwrap().into_iter()
+        .map(|(_, value)| value.to_string())
+        .collect::<Vec<_>>();
+
+    assert!(registers.contains(&"[{str: 3, dex: 2}, UNDEF]".to_owned()), "{:?}", registers);
+
+    let heap = vm.heap(actor).unwrap().into_iter()
+        .map(|(_, value)| value.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(heap[heap.len() - 2..].to_vec(), vec![
+        "{str: 3, dex: 2}",
+        "[{str: 3, dex: 2}, UNDEF]",
+    ]);
+
+    vm.resume(actor).unwrap();
+    assert_eq!(run(&mut vm, 3), vec!["trace 0: [{str: 3, dex: 2}, 5]", "exit 0"]);
+}
+
+#[test]
+fn locate_faults_in_source() {
+    let source = "== start\n\ntrace 1\n\n#nobody <- #hello\n";
+
+    let mut program = compile(source);
+    let label = program.scene_table["test:start"].label;
+    let entry = *program.jump_table.get(label).unwrap();
+    let pos = program.source_of(entry).map(|pos| pos.to_string());
+    assert_eq!(pos, Some("test:start, line 1".to_owned()));
+
+    program.strip();
+    assert!(program.source_of(entry).is_none());
+
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    let log = run(&mut vm, 3);
+    assert_eq!(log.len(), 2, "{:?}", log);
+    assert_eq!(log[0], "trace 0: 1");
+    assert!(log[1].starts_with("hcf 0: NoSuchName"), "{:?}", log);
+    assert!(log[1].ends_with(" at test:start, line 5"), "{:?}", log);
+}
+
+#[test]
+fn vary_lines_across_recursion() {
+    let mut vm = boot(include_str!("../../tests/valid/variations.svr"));
+    vm.spawn("test:start", vec![]).unwrap();
+
+    let log = run(&mut vm, 40);
+    assert_eq!(log.len(), 7, "{:?}", log);
+
+    // The shuffled word is left out, since it's random
+    let lines = [
+        ("Hello. The light is red. Someone waves. The dice show ", ", still {plain braces stay}."),
+        ("Hello again. The light is amber. The dice show ", ", again {plain braces stay}."),
+        ("You're back. The light is green. The dice show ", ", again {plain braces stay}."),
+        ("You're back. The light is red. The dice show ", ", again {plain braces stay}."),
+    ];
+
+    for (line, &(start, end)) in log.iter().zip(lines.iter()) {
+        assert!(line.starts_with(start) && line.ends_with(end), "{:?}", line);
+    }
+
+    assert_eq!(log[4..].to_vec(), vec!["ask > Leave", "trace 0: #done", "exit 0"]);
+}
+
+#[test]
+fn vary_choices_and_kept_strings() {
+    let source = "== start\n\n-> look(0)\n\n== look(N)\n\nlet Weather = > {&sunny|rainy}\ntrace Weather\n\nweave\n| if N == 2 then > Stop\n    trace #done\n| > Look {&up|down}\n    -> look(N + 1)\n;;\n";
+
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    assert_eq!(run(&mut vm, 40), vec![
+        "trace 0: > sunny",
+        "ask > Look up",
+        "trace 0: > rainy",
+        "ask > Look down",
+        "trace 0: > sunny",
+        "ask > Stop | > Look up",
+        "trace 0: #done",
+        "exit 0",
+    ]);
+}
+
+#[test]
+fn return_from_tunnels() {
+    let mut vm = boot(include_str!("../../tests/valid/tunnels.svr"));
+    vm.spawn("test:start", vec![]).unwrap();
+
+    assert_eq!(run(&mut vm, 30), vec![
+        "You walk into town.",
+        "ask > Buy something | > Leave",
+        "trace 0: #apples",
+        "\"Fine, fine. Half price.\"",
+        "You walk back out of town.",
+        "exit 0",
+    ]);
+
+    // The caller's variables are where it left them
+    let mut vm = boot("== start\n\nlet Gold = 3\n-> count(Gold + 1) ->\ntrace Gold\n\n== count(N)\n\ntrace N\n->->\n");
+    vm.spawn("test:start", vec![]).unwrap();
+    assert_eq!(run(&mut vm, 10), vec!["trace 0: 4", "trace 0: 3", "exit 0"]);
+
+    // There's nowhere to return to outside a tunnel
+    let mut vm = boot("== start\n\n->->\n");
+    vm.spawn("test:start", vec![]).unwrap();
+    assert_eq!(run(&mut vm, 10), vec!["hcf 0: NotInTunnel at test:start, line 3"]);
+}
+
+#[test]
+fn keep_mail_for_callers_of_tunnels() {
+    let source = "== start\n\ntrap\n| #ping\n    trace #pong\n;;\n\nspawn helper(Self)\n-> std:dialogue:pause(3) ->\ntrace #back\n-> listening ->\ntrace #back\nwait 3\n\n== listening\n\ntrap\n| #other\n    trace #other\n;;\n\nwait 3\n->->\n\n== helper(Dst)\n\nwait 1\nDst <- #ping\nwait 4\nDst <- #ping\n";
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    // Both pings wait until the caller's trap is armed again
+    let log = run(&mut vm, 30).into_iter()
+        .filter(|line| line.starts_with("trace 0"))
+        .collect::<Vec<_>>();
+
+    assert_eq!(log, vec![
+        "trace 0: #pong",
+        "trace 0: #back",
+        "trace 0: #pong",
+        "trace 0: #back",
+    ]);
+}
+
+#[test]
+fn save_and_restore_globals() {
+    let source = include_str!("../../tests/valid/globals.svr");
+    let global = |vm: &Scheduler, name: &str| {
+        vm.global(name).unwrap().map(|value| value.to_string())
+    };
+
+    let mut vm = boot(source);
+    vm.spawn("test:new_game", vec![]).unwrap();
+    run(&mut vm, 20);
+
+    assert_eq!(global(&vm, "Visitors"), Some("1".to_owned()));
+    assert_eq!(global(&vm, "MetMayor"), Some("true".to_owned()));
+    assert_eq!(vm.visits("test:mayor"), 1);
+
+    let saved = vm.save().unwrap();
+
+    // A fresh story picks up where the saved one left off
+    let mut vm = boot(source);
+    assert_eq!(global(&vm, "Visitors"), None);
+
+    vm.restore(saved).unwrap();
+    assert_eq!(vm.visits("test:mayor"), 1);
+
+    vm.spawn("test:start", vec![]).unwrap();
+    assert_eq!(run(&mut vm, 20), vec![
+        "You knock on the mayor's door.",
+        "exit 0",
+        "trace 1: 2",
+        "exit 1",
+    ]);
+
+    vm.set_global("Visitors", RawValue::Int(10)).unwrap();
+    assert_eq!(global(&vm, "Visitors"), Some("10".to_owned()));
+}
+
+#[test]
+fn refuse_to_read_unset_globals() {
+    let mut vm = boot(include_str!("../../tests/valid/globals.svr"));
+    vm.spawn("test:start", vec![]).unwrap();
+
+    let log = run(&mut vm, 10);
+    assert!(log[0].starts_with("hcf 0: UnsetGlobal"), "{:?}", log);
+    assert!(log[0].ends_with("at test:start, line 10"), "{:?}", log);
+    assert!(vm.global("Visitors").unwrap().is_none());
+}
+
+#[test]
+fn ask_again_after_locked_choice() {
+    let mut vm = boot(include_str!("../../tests/valid/locked_choices.svr"));
+    vm.spawn("test:start", vec![]).unwrap();
+
+    // Try the locked door before walking away
+    let mut tries = 0;
+    let log = run_with(&mut vm, 10, |choices| {
+        tries += 1;
+        choices.iter().find(|choice| choice.available == (tries > 1)).map(|choice| choice.index)
+    });
+
+    assert_eq!(log, vec![
+        "ask (> Open the vault) | (> Boast about the vault) | > Walk away",
+        "ask (> Open the vault) | (> Boast about the vault) | > Walk away",
+        "trace 0: #away",
+        "exit 0",
+    ]);
+
+    // A question with only locked choices still goes to the host
+    let source = "== start\n\nweave\n| if seen(vault) then > Open the vault\n  else > It's locked.\n    trace #opened\n;;\n\ntrace #left\n\n== vault\n\ntrace #vault\n";
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    let mut reasons = vec![];
+    let log = run_with(&mut vm, 3, |choices| {
+        reasons.extend(choices.iter().map(|choice| format!("{:?}", choice.reason)));
+
+        if reasons.len() < 2 {
+            Some(choices[0].index)
+        } else {
+            None
+        }
+    });
+
+    assert_eq!(log, vec!["ask (> Open the vault)"; 2]);
+    assert_eq!(reasons[0], "Some(Str(\"It's locked.\"))");
+
+    // But one with nothing to show answers itself
+    let source = "== start\n\nweave\n| if seen(vault) then > Open the vault\n    trace #opened\n;;\n\ntrace #left\n\n== vault\n\ntrace #vault\n";
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+    assert_eq!(run(&mut vm, 5), vec!["trace 0: #left", "exit 0"]);
+}
+
+#[test]
+fn expire_timed_questions() {
+    let source = include_str!("../../tests/valid/timed_choices.svr");
+
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+    assert_eq!(run_with(&mut vm, 12, |_| None), vec![
+        "The bridge is collapsing. Who do you save?",
+        "ask > Save the mayor | > Save the dog",
+        "You hesitated, and the moment passed.",
+        "trace 0: #nobody",
+        "exit 0",
+    ]);
+
+    // Hold on to the question until it's too late to answer
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    let mut question = None;
+    for _ in 0..3 {
+        vm.advance(1.0);
+        vm.dispatch();
+
+        while let Some(signal) = vm.read() {
+            match signal {
+                OutSignal::Say(token) => vm.write(token.reply().into()),
+                OutSignal::Ask(token) => question = Some(token),
+                _ => (),
+            }
+        }
+    }
+
+    let question = question.unwrap();
+    let deadline = question.deadline().unwrap();
+    assert!(deadline > vm.now());
+
+    let late = deadline - vm.now() + 1.0;
+    vm.advance(late);
+    vm.write(question.reply(0).into());
+
+    assert_eq!(run(&mut vm, 5), vec![
+        "You hesitated, and the moment passed.",
+        "trace 0: #nobody",
+        "exit 0",
+    ]);
+}
+
+#[test]
+fn arbitrate_by_priority() {
+    let source = "== start\n\nspawn chatter\nwait 2\n\n> Listen closely. #main\n\n== chatter\n\n> Nice weather. #ambient\nwait 0\n> Move along. #ambient\n";
+
+    // Lines are held until the test answers them
+    let pump = |vm: &mut Scheduler| {
+        let mut lines = vec![];
+
+        for _ in 0..4 {
+            vm.advance(1.0);
+            vm.dispatch();
+
+            while let Some(signal) = vm.read() {
+                if let OutSignal::Say(token) = signal {
+                    lines.push(token);
+                }
+            }
+        }
+
+        lines
+    };
+
+    let content = |lines: &[SayToken]| {
+        lines.iter().map(SayToken::content).collect::<Vec<_>>()
+    };
+
+    let mut vm = boot(source);
+    vm.arbitrate(vec![
+        Channel { name: "ambient".to_owned(), priority: i32::min_value() },
+        Channel { name: "main".to_owned(), priority: 10 },
+    ]);
+
+    vm.spawn("test:start", vec![]).unwrap();
+
+    // The main channel doesn't wait for a quieter one
+    let mut lines = pump(&mut vm);
+    lines.extend(pump(&mut vm));
+    assert_eq!(content(&lines), vec!["Nice weather.", "Listen closely."]);
+
+    let main = lines.pop().unwrap();
+    let ambient = lines.pop().unwrap();
+
+    // But the next ambient line waits while the main channel is busy
+    vm.write(ambient.reply().into());
+    assert!(pump(&mut vm).is_empty());
+
+    let channels = vm.channels();
+    assert_eq!(channels[0].waiting, vec![ActorId(1)]);
+    assert_eq!(channels[1].speaking, Some(ActorId(0)));
+
+    vm.write(main.reply().into());
+    assert_eq!(content(&pump(&mut vm)), vec!["Move along."]);
+}
+
+#[test]
+fn start_timers_once_questions_are_delivered() {
+    let source = "== start\n\nspawn narrator\nwait 3\n\nweave\n| > Go left\n    trace #left\n| wait 2\n    trace #late\n;;\n\n== narrator\n\n> Listen closely.\n";
+
+    let pump = |vm: &mut Scheduler, rounds: u32| {
+        let mut signals = vec![];
+
+        for _ in 0..rounds {
+            vm.advance(1.0);
+            vm.dispatch();
+
+            while let Some(signal) = vm.read() {
+                signals.push(signal);
+            }
+        }
+
+        signals
+    };
+
+    let mut vm = boot(source);
+    vm.arbitrate(vec![
+        Channel { name: "main".to_owned(), priority: 0 },
+    ]);
+
+    vm.spawn("test:start", vec![]).unwrap();
+
+    // The question waits behind a line the host takes its time over
+    let mut line = None;
+    for signal in pump(&mut vm, 6) {
+        match signal {
+            OutSignal::Say(token) => line = Some(token),
+            OutSignal::Ask(_) => panic!("Question wasn't held back"),
+            _ => (),
+        }
+    }
+
+    vm.write(line.unwrap().reply().into());
+
+    let mut question = None;
+    for signal in pump(&mut vm, 1) {
+        if let OutSignal::Ask(token) = signal {
+            question = Some(token);
+        }
+    }
+
+    // So its clock only starts now, instead of having run out in the queue
+    let question = question.unwrap();
+    assert_eq!(question.deadline(), Some(vm.now() + 2.0));
+
+    let mut log = vec![];
+    for signal in pump(&mut vm, 4) {
+        match signal {
+            OutSignal::Exit(ActorId(id)) => log.push(format!("exit {}", id)),
+            OutSignal::Trace(ActorId(id), value, _) => {
+                log.push(format!("trace {}: {}", id, value));
+            },
+            _ => (),
+        }
+    }
+
+    assert_eq!(log, vec!["trace 0: #late", "exit 0"]);
+}
+
+#[test]
+fn arbitrate_without_channels() {
+    let source = include_str!("../../tests/valid/ambient_barks.svr")
+        .replace("ambient_barks:", "test:");
+
+    // With nowhere to queue them, lines go straight to the host
+    let mut vm = boot(&source);
+    vm.arbitrate(vec![]);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    // The two actors can speak in either order
+    let mut log = run(&mut vm, 10);
+    log.sort();
+
+    assert_eq!(log, vec![
+        "Listen closely, this is important.",
+        "Nice weather today. Move along.",
+        "exit 0",
+        "exit 1",
+    ]);
+}
+
+#[test]
+fn compare_only_values_of_one_type() {
+    let source = "== start\n\nlet Pid = spawn target\nwait 1\n\nPid <- #hit, 3\nPid <- #say, > three\nPid <- #hit, #three\nPid <- #hit, true\nPid <- #hit, 2\n\n== target\n\ntrap\n| #hit, 3\n    trace #int\n| #say, Text when Text == > three\n    trace #str\n| #hit, N when N == 2\n    trace #two\n;;\n\nwait 5\n";
+
+    let mut vm = boot(source);
+    vm.spawn("test:start", vec![]).unwrap();
+
+    // A guard skips messages it can't compare, rather than stopping
+    let mut log = run(&mut vm, 12);
+    log.sort();
+
+    assert_eq!(log, vec![
+        "exit 0",
+        "exit 1",
+        "trace 1: #int",
+        "trace 1: #str",
+        "trace 1: #two",
+    ]);
+
+    // Elsewhere, other types are unequal, but bools and ints don't mix
+    let source = "== start(Value)\n\nweave\n| if Value == #three then > Three\n    trace #three\n| if Value == 1 then > One\n    trace #one\n;;\n";
+    let answer = |value: RawValue| {
+        let mut vm = boot(source);
+        vm.spawn("test:start", vec![value]).unwrap();
+        run(&mut vm, 5)
+    };
+
+    assert_eq!(answer(RawValue::Int(1)), vec!["ask > One", "trace 0: #one", "exit 0"]);
+    assert_eq!(answer(RawValue::Bool(true)), vec![
+        "hcf 0: TypeMismatch(Int(1), Bool) at test:start, line 3",
+    ]);
+}
+
+#[test]
+fn roll_dice_within_range() {
+    let roll = |count: i32, sides: i32, seed: u32| {
+        let mut vm = boot(&format!("== start\n\ntrace roll({}, {})\n", count, sides));
+        vm.reseed(seed);
+        vm.spawn("test:start", vec![]).unwrap();
+        run(&mut vm, 3)
+    };
+
+    for seed in 0..20 {
+        let log = roll(2, 6, seed);
+        let total = log[0]["trace 0: ".len()..].parse::<i32>().unwrap();
+        assert!(total >= 2 && total <= 12, "{:?}", log);
+    }
+
+    assert_eq!(roll(1, 0, 0), vec![
+        "hcf 0: InvalidRoll { count: 1, sides: 0 } at test:start, line 3",
+    ]);
+
+    assert_eq!(roll(5, i32::max_value(), 0), vec![
+        "hcf 0: RollOverflow { count: 5, sides: 2147483647 } at test:start, line 3",
+    ]);
+}
+
+#[test]
+fn match_values_against_patterns() {
+    let mut vm = boot(include_str!("../../tests/valid/matches.svr"));
+    vm.spawn("test:start", vec![]).unwrap();
+
+    assert_eq!(run(&mut vm, 5), vec![
+        "trace 0: #smile",
+        "trace 0: #ann",
+        "exit 0",
+    ]);
+}
+
+#[test]
+fn translate_every_exported_string() {
+    let source = "== start\n\n> Hello there.\n\nweave\n| > Wave back\n    > You wave.\n;;\n";
+
+    let program = || ast::Program {
+        modules: vec![
+            (Modpath(vec!["test".to_owned()]), Module::parse(source).unwrap()),
+        ],
+    };
+
+    let table = program().strings().unwrap().into_iter().map(|entry| {
+        (entry.id, entry.text.to_uppercase())
+    }).collect();
+
+    let mut program = program().compile().unwrap();
+    assert!(program.translate(&table).is_empty());
+
+    let mut vm = program.init().unwrap();
+    vm.spawn("test:start", vec![]).unwrap();
+
+    assert_eq!(run(&mut vm, 6), vec![
+        "HELLO THERE.",
+        "ask > WAVE BACK",
+        "YOU WAVE.",
+        "exit 0",
+    ]);
+}
+
+#[test]
+fn reply_from_bundled_modules() {
+    // Runs `script` with a trap that traces every reply it's sent
+    let replies = |script: &str, rounds: u32| {
+        let source = format!("== start\n\ntrap\n| Tag\n    trace Tag\n| Tag, Value\n    trace [Tag, Value]\n| Tag, A, B, C\n    trace [Tag, A, B, C]\n;;\n\n{}\nwait {}\n", script, rounds);
+        let mut vm = boot(&source);
+        vm.spawn("test:start", vec![]).unwrap();
+
+        run(&mut vm, rounds + 5).into_iter()
+            .filter(|line| line.starts_with("trace 0: "))
+            .map(|line| line["trace 0: ".len()..].to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(replies("spawn std:list:reverse([1, 2, 3], Self)", 10), vec![
+        "[#reversed, [3, 2, 1]]",
+    ]);
+
+    assert_eq!(replies("spawn std:list:sum([1, 2, 3], Self)", 10), vec![
+        "[#sum, 6]",
+    ]);
+
+    assert_eq!(replies("spawn std:list:each([#a, #b], Self)", 10), vec![
+        "[#item, #a]",
+        "[#item, #b]",
+        "#done",
+    ]);
+
+    // Anything but a list gets an answer, rather than none at all
+    for helper in ["reverse", "sum", "each"].iter() {
+        let script = format!("spawn std:list:{}(#oops, Self)", helper);
+        assert_eq!(replies(&script, 5), vec!["[#not_a_list, #oops]"]);
+    }
+
+    assert_eq!(replies("spawn std:timer:after(2, Self, #ring)", 5), vec![
+        "#ring",
+    ]);
+
+    let ticks = replies("let Timer = spawn std:timer:every(2, Self, #tick)\nwait 9\nTimer <- #stop", 10);
+    // It stops ticking long before the caller stops listening
+    assert!(ticks.len() >= 2 && ticks.len() <= 5, "{:?}", ticks);
+    assert!(ticks.iter().all(|tick| tick == "#tick"), "{:?}", ticks);
+
+    assert_eq!(replies("spawn std:random:dice(3, 1, Self)", 5), vec![
+        "[#rolled, 3]",
+    ]);
+
+    assert_eq!(replies("spawn std:random:pick([#only], Self)", 5), vec![
+        "[#picked, #only]",
+    ]);
+
+    // The dialogue helpers are tunnels, so their replies come to Self
+    assert_eq!(replies("-> std:dialogue:confirm(Self) ->", 5), vec![
+        "#yes",
+    ]);
+
+    assert_eq!(replies("-> std:dialogue:pause(2) ->\ntrace #after", 5), vec![
+        "#after",
+    ]);
+
+    assert_eq!(replies("spawn std:version:check(Self)", 5), vec![
+        format!("[#version, {}]", ::stdlib::VERSION.replace(".", ", ")),
+    ]);
+}
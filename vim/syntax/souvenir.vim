@@ -12,16 +12,18 @@ syn match souvenirNumber /\<\d\+\>/
 syn match souvenirRandom /\<\d\+d\d\+\>/
 syn match souvenirArithmetic /-\|+\|\*\|<=\?/
 
-syn region souvenirString start=/> / end=/$/ contains=tetanusBraces,tetanusTag
+syn region souvenirString start=/> / end=/$/ contains=tetanusBraces,tetanusTag,souvenirLineTag
 syn region souvenirComment start=/--/ end=/$/
 
 syn region tetanusTagBody matchgroup=tetanusDelimiter start=/{/ end=/}/ contained contains=tetanusBraces,tetanusTag
 syn region tetanusBraces matchgroup=tetanusDelimiter start=/{/ matchgroup=tetanusDelimiter end=/}/ contained contains=souvenirVariableName
 syn match tetanusTag /\\\w\+/ contained nextgroup=tetanusTagBody
+syn match souvenirLineTag /\s\zs#\l[a-z0-9_]*\ze\(\s\+#\l[a-z0-9_]*\)*\s*$/ contained
 
-syn keyword souvenirMatch trap given listen weave branch when if then
-syn keyword souvenirCommand let trace wait disarm spawn
-syn keyword souvenirKeyword from
+syn keyword souvenirMatch trap given listen weave branch when if then else
+syn keyword souvenirCommand let trace wait disarm spawn register join leave
+syn keyword souvenirKeyword from group seen visits
+syn keyword souvenirInclude import export as
 
 " Must come before SceneDef
 syn match souvenirEquals /=/
@@ -37,17 +39,20 @@ syn match souvenirLabel /'\l\w*/
 syn match souvenirMacro /?\u[A-Z0-9_]*/
 syn match souvenirAtom /#\l[a-z0-9_]*/
 syn match souvenirVariableName /\u[A-Za-z]*/
+syn match souvenirGlobalName /\$\u[A-Za-z0-9]*/
 syn match souvenirChoice /|/
 syn match souvenirSend /<-/
 syn match souvenirEnd /;;/
 
 syn keyword souvenirSpecialVar Self _
+syn keyword souvenirBoolean true false
 
 hi def link souvenirChoice Label
 hi def link souvenirDivert Statement
 
 hi def link souvenirString String
 hi def link souvenirNumber Number
+hi def link souvenirBoolean Boolean
 hi def link souvenirRandom Number
 hi def link souvenirSpecialVar Special
 
@@ -57,6 +62,7 @@ hi def link souvenirArithmetic Operator
 hi def link souvenirMatch Conditional
 hi def link souvenirCommand Statement
 hi def link souvenirKeyword Keyword
+hi def link souvenirInclude Include
 
 hi def link souvenirModName PreProc
 hi def link souvenirModSep Delimiter
@@ -67,9 +73,11 @@ hi def link souvenirLabel Tag
 hi def link souvenirParen Delimiter
 hi def link souvenirAtom Constant
 hi def link souvenirVariableName Identifier
+hi def link souvenirGlobalName Identifier
 hi def link souvenirSend Statement
 hi def link souvenirEnd Delimiter
 
+hi def link souvenirLineTag Constant
 hi def link tetanusTag PreProc
 hi def link tetanusTagBody String
 hi def link tetanusDelimiter Delimiter